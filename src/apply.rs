@@ -0,0 +1,322 @@
+use crate::config::{Config, PathStyle};
+use crate::{links, split_book};
+use anyhow::{bail, Context, Error};
+use mdbook::book::{Book, BookItem, Chapter};
+use mdbook::MDBook;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A chapter listed in `SUMMARY.md` in place of the chapter it was split from.
+#[derive(Debug)]
+struct Entry {
+    /// Nesting depth below the first chapter split from the same source file.
+    depth: usize,
+    name: String,
+    path: Option<PathBuf>,
+}
+
+/// Content of the source files of all chapters in `book` before splitting.
+fn originals(book: &Book) -> HashMap<PathBuf, String> {
+    book.iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(Chapter {
+                source_path: Some(source_path),
+                content,
+                ..
+            }) => Some((source_path.clone(), content.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Chapters split from each source file of `book` in reading order.
+fn entries(book: &Book) -> HashMap<PathBuf, Vec<Entry>> {
+    fn collect(
+        items: &[BookItem],
+        parent: Option<(&Path, usize)>,
+        entries: &mut HashMap<PathBuf, Vec<Entry>>,
+    ) {
+        for item in items {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let Some(source_path) = &chapter.source_path else {
+                collect(&chapter.sub_items, None, entries);
+                continue;
+            };
+
+            let depth = match parent {
+                Some((parent, depth)) if parent == source_path => depth + 1,
+                _ => 0,
+            };
+
+            entries.entry(source_path.clone()).or_default().push(Entry {
+                depth,
+                name: chapter.name.clone(),
+                path: chapter.path.clone(),
+            });
+
+            collect(&chapter.sub_items, Some((source_path, depth)), entries);
+        }
+    }
+
+    let mut entries = HashMap::new();
+    collect(&book.sections, None, &mut entries);
+    entries
+}
+
+/// Lines listing the chapters split from the chapter linked in the `SUMMARY.md` `line`, or
+/// `None` if the line stays as it is.
+fn replace_line(line: &str, entries: &HashMap<PathBuf, Vec<Entry>>) -> Option<String> {
+    let (prefix, link) = line.split_at(line.find('[')?);
+    let target = link.split_once("](")?.1.split_once(')')?.0.trim();
+    let target = Path::new(target.strip_prefix("./").unwrap_or(target));
+    let entries = entries.get(target)?;
+
+    if let [Entry {
+        depth: 0,
+        path: Some(path),
+        ..
+    }] = entries.as_slice()
+    {
+        if path == target {
+            return None;
+        }
+    }
+
+    let marker = prefix.trim_start();
+    let indent = &prefix[..prefix.len() - marker.len()];
+    let mut lines = String::new();
+
+    for entry in entries {
+        // Prefix and suffix chapters can't be nested.
+        let depth = if marker.is_empty() { 0 } else { entry.depth };
+        let name = entry.name.replace('[', "\\[").replace(']', "\\]");
+        let path = entry
+            .path
+            .as_ref()
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+
+        lines.push_str(&format!(
+            "{indent}{}{marker}[{name}]({path})\n",
+            "  ".repeat(depth)
+        ));
+    }
+
+    Some(lines)
+}
+
+/// Replace the links to split chapters in `summary` with links to the chapters split from them.
+fn rewrite_summary(summary: &str, entries: &HashMap<PathBuf, Vec<Entry>>) -> String {
+    summary
+        .split_inclusive('\n')
+        .map(|line| replace_line(line, entries).unwrap_or_else(|| line.to_string()))
+        .collect()
+}
+
+/// `book_toml` without the `[preprocessor.split]` table and its subtables, so the book isn't split
+/// again once the split is applied.
+fn remove_preprocessor(book_toml: &str) -> Result<String, Error> {
+    let mut removing = false;
+    let content: String = book_toml
+        .split_inclusive('\n')
+        .filter(|line| {
+            let line = line.trim();
+
+            if line.starts_with('[') {
+                let table = line.trim_matches(|c| c == '[' || c == ']').trim();
+                removing =
+                    table == "preprocessor.split" || table.starts_with("preprocessor.split.");
+            }
+
+            !removing
+        })
+        .collect();
+
+    let value: toml::Value = toml::from_str(&content)?;
+
+    if value
+        .get("preprocessor")
+        .and_then(|preprocessor| preprocessor.get("split"))
+        .is_some()
+    {
+        bail!(
+            "Unable to remove the split preprocessor from book.toml, move its settings into a \
+             [preprocessor.split] table or remove them by hand after applying the split"
+        );
+    }
+
+    Ok(content)
+}
+
+/// Split the source files of the book at `root` the way the preprocessor would, writing one
+/// Markdown file per split chapter, removing the split source files and listing the new files in
+/// `SUMMARY.md`. Source files that don't split into several chapters keep their path and only get
+/// their links updated. The `[preprocessor.split]` table is removed from `book.toml` afterwards.
+/// Chapters are named after their headings unless `path-style` is `source-index`.
+pub fn apply(root: &Path) -> Result<(), Error> {
+    let md_book = MDBook::load(root)?;
+    let mut config = Config::from_book_config(&md_book.config, "")?;
+
+    if config.path_style == PathStyle::Hash {
+        config.path_style = PathStyle::Slug;
+    }
+
+    let book_toml_file = root.join("book.toml");
+    let book_toml = if book_toml_file.exists() {
+        let book_toml = std::fs::read_to_string(&book_toml_file)
+            .with_context(|| format!("Unable to read {}", book_toml_file.display()))?;
+        Some(remove_preprocessor(&book_toml)?).filter(|content| *content != book_toml)
+    } else {
+        None
+    };
+
+    let src = md_book.source_dir();
+    let originals = originals(&md_book.book);
+    let (book, sources) = split_book(md_book.book, &config, "md", true)?;
+    let entries = entries(&book);
+
+    let mut files = vec![];
+    let mut paths = HashSet::new();
+
+    for item in book.iter() {
+        let BookItem::Chapter(Chapter {
+            path: Some(path),
+            source_path: Some(source_path),
+            content,
+            ..
+        }) = item
+        else {
+            continue;
+        };
+
+        paths.insert(path);
+
+        let single = entries
+            .get(source_path)
+            .is_some_and(|entries| entries.len() == 1);
+        let content = match (single, originals.get(source_path)) {
+            (true, Some(original)) => {
+                // Only links to chapters that moved change in source files that didn't split.
+                let mut chapter = Chapter {
+                    content: original.clone(),
+                    path: Some(path.clone()),
+                    source_path: Some(source_path.clone()),
+                    ..Default::default()
+                };
+                links::rewrite_chapter(&mut chapter, &sources, &config, "md");
+                chapter.content
+            }
+            _ => content.clone(),
+        };
+
+        if path == source_path && originals.get(source_path) == Some(&content) {
+            continue;
+        }
+
+        let file = src.join(path);
+
+        if !originals.contains_key(path) && file.exists() {
+            bail!(
+                "Refusing to overwrite {}, which is not a chapter of the book",
+                file.display()
+            );
+        }
+
+        files.push((file, content));
+    }
+
+    for (file, content) in files {
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Unable to create {}", dir.display()))?;
+        }
+
+        std::fs::write(&file, content)
+            .with_context(|| format!("Unable to write {}", file.display()))?;
+        println!("Wrote {}", file.display());
+    }
+
+    for source_path in originals.keys().filter(|path| !paths.contains(path)) {
+        let file = src.join(source_path);
+        std::fs::remove_file(&file)
+            .with_context(|| format!("Unable to remove {}", file.display()))?;
+        println!("Removed {}", file.display());
+    }
+
+    let summary_file = src.join("SUMMARY.md");
+    let summary = std::fs::read_to_string(&summary_file)
+        .with_context(|| format!("Unable to read {}", summary_file.display()))?;
+    std::fs::write(&summary_file, rewrite_summary(&summary, &entries))
+        .with_context(|| format!("Unable to write {}", summary_file.display()))?;
+    println!("Wrote {}", summary_file.display());
+
+    if let Some(book_toml) = book_toml {
+        std::fs::write(&book_toml_file, book_toml)
+            .with_context(|| format!("Unable to write {}", book_toml_file.display()))?;
+        println!("Wrote {}", book_toml_file.display());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rewrite_summary_entries() {
+        let entry = |depth, name: &str, path: Option<&str>| Entry {
+            depth,
+            name: name.to_string(),
+            path: path.map(PathBuf::from),
+        };
+        let entries = HashMap::from([
+            (
+                PathBuf::from("intro.md"),
+                vec![
+                    entry(0, "Intro", Some("intro/intro.md")),
+                    entry(0, "Setup", None),
+                ],
+            ),
+            (
+                PathBuf::from("guide.md"),
+                vec![
+                    entry(0, "Guide", Some("guide/guide.md")),
+                    entry(1, "Usage [CLI]", Some("guide/usage-cli.md")),
+                ],
+            ),
+            (
+                PathBuf::from("other.md"),
+                vec![entry(0, "Other", Some("other.md"))],
+            ),
+        ]);
+        let summary =
+            "# Summary\n\n[Intro](./intro.md)\n\n- [Guide](guide.md)\n    - [Other](other.md)\n";
+
+        assert_eq!(
+            rewrite_summary(summary, &entries),
+            "# Summary\n\n\
+             [Intro](intro/intro.md)\n\
+             [Setup]()\n\n\
+             - [Guide](guide/guide.md)\n  \
+               - [Usage \\[CLI\\]](guide/usage-cli.md)\n    \
+             - [Other](other.md)\n"
+        );
+    }
+
+    #[test]
+    fn remove_preprocessor_table() {
+        let book_toml = "[book]\ntitle = \"Deck\"\n\n\
+                         [preprocessor.split]\nlevel = 2\n\n\
+                         [preprocessor.split.renderer.pdf]\nlevel = 1\n\n\
+                         [output.html]\n";
+        assert_eq!(
+            remove_preprocessor(book_toml).unwrap(),
+            "[book]\ntitle = \"Deck\"\n\n[output.html]\n"
+        );
+
+        assert!(remove_preprocessor("[preprocessor]\nsplit = { level = 2 }\n").is_err());
+    }
+}
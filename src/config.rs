@@ -0,0 +1,292 @@
+use anyhow::{anyhow, Context, Error};
+use glob::{MatchOptions, Pattern};
+use mdbook::book::Chapter;
+use pulldown_cmark::{HeadingLevel, Options};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use toml::value::{Table, Value};
+
+/// Options read from the `[preprocessor.split]` table of `book.toml`, with the settings of the
+/// `[preprocessor.split.renderer.<name>]` table of the current renderer taking precedence.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Only split chapters for these renderers, if given.
+    pub renderers: Option<Vec<String>>,
+    /// Heading level or levels that start a new chapter.
+    pub level: Levels,
+    /// Nest chapters split at deeper levels below those split at shallower levels.
+    pub nested: bool,
+    /// How output paths of split chapters are derived.
+    pub path_style: PathStyle,
+    /// Also split at thematic breaks (`---`).
+    pub split_on_rule: bool,
+    /// HTML comment that splits a chapter without a heading, e.g. `<!-- slide -->`.
+    pub marker: Option<String>,
+    /// Name of chapters split at a marker, `{parent}` is replaced with the name of the preceding
+    /// chapter with a heading and `{n}` with the position after it.
+    pub untitled: String,
+    /// Merge chapters with fewer words into the chapter before them.
+    pub min_words: usize,
+    /// Split chapters with more words at the next deeper heading level.
+    pub max_words: Option<usize>,
+    /// Read front matter right after split headings.
+    pub front_matter: bool,
+    /// What happens to content before the first split heading.
+    pub preamble: Preamble,
+    /// File relative to the book root to write redirects from split source files to.
+    pub redirect_file: Option<PathBuf>,
+    /// Forward anchors of split source files to the chapters they moved to.
+    pub redirect_anchors: bool,
+    /// Print the chapters split from each source file without page breaks in between.
+    pub print_unsplit: bool,
+    /// Only split chapters whose source path matches one of these patterns, if any are given.
+    pub include: Globs,
+    /// Never split chapters whose source path matches one of these patterns.
+    pub exclude: Globs,
+    /// Markdown extensions used when parsing chapters.
+    pub markdown: Markdown,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            renderers: None,
+            level: Levels::default(),
+            nested: false,
+            path_style: PathStyle::default(),
+            split_on_rule: false,
+            marker: None,
+            untitled: "{parent} ({n})".to_string(),
+            min_words: 0,
+            max_words: None,
+            front_matter: false,
+            preamble: Preamble::default(),
+            redirect_file: None,
+            redirect_anchors: false,
+            print_unsplit: false,
+            include: Globs::default(),
+            exclude: Globs::default(),
+            markdown: Markdown::default(),
+        }
+    }
+}
+
+/// Merge `overrides` into `table`, replacing values except for nested tables, which are merged.
+fn merge(table: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(Value::Table(nested)), Value::Table(value)) => merge(nested, value),
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Read the `[preprocessor.split]` table from the book configuration, applying the overrides
+    /// for `renderer`.
+    pub fn from_book_config(config: &mdbook::Config, renderer: &str) -> Result<Self, Error> {
+        let mut table = match config.get("preprocessor.split") {
+            Some(Value::Table(table)) => table.clone(),
+            Some(_) => return Err(anyhow!("preprocessor.split must be a table")),
+            None => Table::new(),
+        };
+
+        if let Some(Value::Table(mut renderers)) = table.remove("renderer") {
+            if let Some(Value::Table(overrides)) = renderers.remove(renderer) {
+                merge(&mut table, overrides);
+            }
+        }
+
+        let mut split: Self = Value::Table(table)
+            .try_into()
+            .context("Invalid configuration of the split preprocessor")?;
+
+        if split.markdown.smart_punctuation.is_none() {
+            let enabled = |key| config.get(key).and_then(|value| value.as_bool());
+            split.markdown.smart_punctuation = enabled("output.html.smart-punctuation")
+                .or_else(|| enabled("output.html.curly-quotes"));
+        }
+
+        Ok(split)
+    }
+
+    /// Returns `true` if chapters are split for `renderer`.
+    pub fn splits_for(&self, renderer: &str) -> bool {
+        self.renderers
+            .as_ref()
+            .is_none_or(|renderers| renderers.iter().any(|name| name == renderer))
+    }
+
+    /// Returns `true` if `chapter` is selected for splitting by the include and exclude patterns.
+    pub fn selects(&self, chapter: &Chapter) -> bool {
+        let Some(source_path) = &chapter.source_path else {
+            return false;
+        };
+
+        (self.include.0.is_empty() || self.include.matches(source_path))
+            && !self.exclude.matches(source_path)
+    }
+}
+
+/// Glob patterns matched against source paths relative to the book's source directory.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct Globs(Vec<Pattern>);
+
+impl Globs {
+    /// Returns `true` if any of the patterns matches `path`.
+    pub fn matches(&self, path: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+
+        self.0
+            .iter()
+            .any(|pattern| pattern.matches_path_with(path, options))
+    }
+}
+
+impl TryFrom<Vec<String>> for Globs {
+    type Error = glob::PatternError;
+
+    fn try_from(patterns: Vec<String>) -> Result<Self, Self::Error> {
+        patterns
+            .iter()
+            .map(|pattern| Pattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+}
+
+/// Policy for content before the first split heading of a chapter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preamble {
+    /// Prepend it to the first split chapter.
+    Attach,
+    /// Keep it as a chapter named after the original chapter.
+    #[default]
+    Intro,
+    /// Remove it.
+    Drop,
+}
+
+/// Markdown extensions, enabled the same way mdBook enables them when rendering chapters.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Markdown {
+    pub tables: bool,
+    pub footnotes: bool,
+    pub strikethrough: bool,
+    pub tasklists: bool,
+    pub heading_attributes: bool,
+    /// Defaults to `output.html.smart-punctuation`.
+    pub smart_punctuation: Option<bool>,
+}
+
+impl Markdown {
+    /// Options for `pulldown_cmark::Parser` with the enabled extensions.
+    pub fn parser_options(&self) -> Options {
+        let mut options = Options::empty();
+        options.set(Options::ENABLE_TABLES, self.tables);
+        options.set(Options::ENABLE_FOOTNOTES, self.footnotes);
+        options.set(Options::ENABLE_STRIKETHROUGH, self.strikethrough);
+        options.set(Options::ENABLE_TASKLISTS, self.tasklists);
+        options.set(Options::ENABLE_HEADING_ATTRIBUTES, self.heading_attributes);
+        options.set(
+            Options::ENABLE_SMART_PUNCTUATION,
+            self.smart_punctuation.unwrap_or(false),
+        );
+        options
+    }
+}
+
+impl Default for Markdown {
+    fn default() -> Self {
+        Self {
+            tables: true,
+            footnotes: true,
+            strikethrough: true,
+            tasklists: true,
+            heading_attributes: true,
+            smart_punctuation: None,
+        }
+    }
+}
+
+/// Scheme for naming the output files of split chapters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PathStyle {
+    /// SHA-256 of the chapter name.
+    #[default]
+    Hash,
+    /// Slugified chapter name below a directory named after the source chapter.
+    Slug,
+    /// Position within the source chapter below a directory named after it.
+    SourceIndex,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LevelsRepr {
+    Single(usize),
+    Multiple(Vec<usize>),
+}
+
+/// Set of heading levels at which a chapter is split.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "LevelsRepr")]
+pub struct Levels(Vec<HeadingLevel>);
+
+impl Levels {
+    /// Create a set from heading levels given as numbers from 1 to 6.
+    pub fn new(levels: Vec<usize>) -> Result<Self, Error> {
+        if levels.is_empty() {
+            return Err(anyhow!("at least one split level must be given"));
+        }
+
+        let mut levels = levels
+            .into_iter()
+            .map(|level| {
+                HeadingLevel::try_from(level)
+                    .map_err(|_| anyhow!("{level} is not a valid heading level"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        levels.sort();
+        levels.dedup();
+
+        Ok(Self(levels))
+    }
+
+    pub fn contains(&self, level: HeadingLevel) -> bool {
+        self.0.contains(&level)
+    }
+
+    /// Nesting depth of `level` among the configured levels, starting at zero.
+    pub fn depth(&self, level: HeadingLevel) -> usize {
+        self.0.iter().take_while(|&&l| l < level).count()
+    }
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self(vec![HeadingLevel::H1])
+    }
+}
+
+impl TryFrom<LevelsRepr> for Levels {
+    type Error = Error;
+
+    fn try_from(repr: LevelsRepr) -> Result<Self, Self::Error> {
+        match repr {
+            LevelsRepr::Single(level) => Self::new(vec![level]),
+            LevelsRepr::Multiple(levels) => Self::new(levels),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::config::{Config, Levels};
+use pulldown_cmark::Event;
+
+/// Instruction embedded in a chapter as an HTML comment.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Directive {
+    /// `<!-- split: off -->` keeps the whole chapter as it is.
+    Off,
+    /// `<!-- split: level=2 -->` splits the chapter at other levels than configured.
+    Level(Levels),
+    /// `<!-- nosplit -->` keeps the following heading in the current chapter.
+    NoSplit,
+}
+
+impl Directive {
+    /// Parse the directive in `event` if it is one.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::Html(html) => Self::parse(html),
+            _ => None,
+        }
+    }
+
+    fn parse(html: &str) -> Option<Self> {
+        let comment = html
+            .trim()
+            .strip_prefix("<!--")?
+            .strip_suffix("-->")?
+            .trim();
+
+        if comment == "nosplit" {
+            return Some(Self::NoSplit);
+        }
+
+        let setting = comment.strip_prefix("split:")?.trim();
+
+        if setting == "off" {
+            return Some(Self::Off);
+        }
+
+        let levels = setting
+            .strip_prefix("level")?
+            .trim_start()
+            .strip_prefix('=')?
+            .split(',')
+            .map(|level| level.trim().parse())
+            .collect::<Result<Vec<usize>, _>>()
+            .ok()?;
+
+        Levels::new(levels).ok().map(Self::Level)
+    }
+}
+
+/// Apply the chapter-wide directives at the top of the chapter `name`, before anything but HTML,
+/// to `config`, returning `None` if splitting is turned off for the chapter. Only the first
+/// `level` directive counts.
+pub(crate) fn apply(name: &str, content: &str, config: &Config) -> Option<Config> {
+    let mut config = config.clone();
+    let mut level = false;
+
+    for event in pulldown_cmark::Parser::new_ext(content, config.markdown.parser_options()) {
+        match Directive::from_event(&event) {
+            Some(Directive::Off) => return None,
+            Some(Directive::Level(_)) if level => {
+                eprintln!("Warning: Ignoring repeated split level directive in chapter \"{name}\"");
+            }
+            Some(Directive::Level(levels)) => {
+                config.level = levels;
+                level = true;
+            }
+            _ if matches!(event, Event::Html(_)) => {}
+            _ => break,
+        }
+    }
+
+    Some(config)
+}
+
+/// `content` without the top-level directives, which aren't part of the content of split
+/// chapters either.
+pub(crate) fn strip(content: &str, config: &Config) -> String {
+    let parser = pulldown_cmark::Parser::new_ext(content, config.markdown.parser_options());
+    let mut stripped = String::with_capacity(content.len());
+    let mut start = 0;
+    let mut depth = 0;
+
+    for (event, range) in parser.into_offset_iter() {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ if depth == 0 && Directive::from_event(&event).is_some() => {
+                stripped.push_str(&content[start..range.start]);
+                start = range.end;
+            }
+            _ => {}
+        }
+    }
+
+    stripped.push_str(&content[start..]);
+    stripped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_directives() {
+        assert_eq!(
+            Directive::parse("<!-- split: off -->\n"),
+            Some(Directive::Off)
+        );
+        assert_eq!(Directive::parse("<!--nosplit-->"), Some(Directive::NoSplit));
+        assert_eq!(
+            Directive::parse("<!-- split: level = 1, 2 -->"),
+            Some(Directive::Level(Levels::new(vec![1, 2]).unwrap()))
+        );
+        assert_eq!(Directive::parse("<!-- split: level=9 -->"), None);
+        assert_eq!(Directive::parse("<!-- a comment -->"), None);
+    }
+}
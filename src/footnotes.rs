@@ -0,0 +1,93 @@
+use crate::section::Section;
+use mdbook::book::Chapter;
+use pulldown_cmark::{Event, Tag};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+
+/// A footnote definition within a chapter's source.
+struct Definition {
+    range: Range<usize>,
+    /// Index of the section containing the definition.
+    section: usize,
+    /// Footnotes referenced from within the definition itself.
+    references: Vec<String>,
+}
+
+/// Labels of all footnotes needed by a section referencing `labels`, including those referenced
+/// from the needed definitions themselves.
+fn needed(labels: &[String], definitions: &HashMap<String, Definition>) -> BTreeSet<String> {
+    let mut needed = BTreeSet::new();
+    let mut pending = labels.to_vec();
+
+    while let Some(label) = pending.pop() {
+        if needed.insert(label.clone()) {
+            if let Some(definition) = definitions.get(&label) {
+                pending.extend(definition.references.iter().cloned());
+            }
+        }
+    }
+
+    needed
+}
+
+/// Move footnote definitions into the sections referencing them. A definition referenced from
+/// several sections is duplicated into each of them, unreferenced definitions stay in place.
+pub(crate) fn relocate(sections: &mut [Section], chapter: &Chapter) {
+    let mut definitions = HashMap::new();
+    let mut references = vec![vec![]; sections.len()];
+
+    for (index, section) in sections.iter().enumerate() {
+        let mut current: Option<(String, Definition)> = None;
+
+        for (event, range) in &section.events {
+            match event {
+                Event::Start(Tag::FootnoteDefinition(label)) => {
+                    let definition = Definition {
+                        range: range.clone(),
+                        section: index,
+                        references: vec![],
+                    };
+                    current = Some((label.to_string(), definition));
+                }
+                Event::End(Tag::FootnoteDefinition(_)) => {
+                    if let Some((label, definition)) = current.take() {
+                        definitions.insert(label, definition);
+                    }
+                }
+                Event::FootnoteReference(label) => match &mut current {
+                    Some((_, definition)) => definition.references.push(label.to_string()),
+                    None => references[index].push(label.to_string()),
+                },
+                _ => {}
+            }
+        }
+    }
+
+    let needed = references
+        .iter()
+        .map(|labels| needed(labels, &definitions))
+        .collect::<Vec<_>>();
+
+    let mut ordered = definitions.iter().collect::<Vec<_>>();
+    ordered.sort_by_key(|(_, definition)| definition.range.start);
+
+    for (label, definition) in ordered {
+        if !needed.iter().any(|labels| labels.contains(label)) {
+            eprintln!(
+                "Warning: Footnote [^{label}] in chapter \"{}\" is never referenced",
+                chapter.name
+            );
+            continue;
+        }
+
+        for (index, section) in sections.iter_mut().enumerate() {
+            let needs = needed[index].contains(label);
+
+            if index == definition.section && !needs {
+                section.skip.push(definition.range.clone());
+            } else if index != definition.section && needs {
+                section.extra.push(definition.range.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{bail, Error};
+use serde::Deserialize;
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Settings of a single split chapter given in a block right after its heading.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct FrontMatter {
+    /// Name of the chapter instead of the heading text.
+    pub name: Option<String>,
+    /// Path of the chapter relative to the `src` directory instead of the generated one.
+    pub path: Option<PathBuf>,
+    /// Turn the chapter into a draft chapter without content.
+    pub draft: bool,
+    /// CSS classes of a `<div>` wrapped around the chapter's content.
+    pub classes: Vec<String>,
+}
+
+/// Opening and closing lines of YAML, TOML and HTML comment front matter.
+const DELIMITERS: [(&str, &str); 3] = [("---", "---"), ("+++", "+++"), ("<!-- split", "-->")];
+
+impl FrontMatter {
+    /// Parse the front matter `block` found by [`find`].
+    pub fn parse(block: &str) -> Result<Self, Error> {
+        let mut lines = block.trim().lines();
+        let open = lines.next().unwrap_or_default().trim_end();
+        let body = lines
+            .take_while(|line| {
+                !DELIMITERS
+                    .iter()
+                    .any(|(_, close)| line.trim_end() == *close)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if body.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        match open {
+            "+++" => Ok(toml::from_str(&body)?),
+            "---" | "<!-- split" => Ok(serde_yaml::from_str(&body)?),
+            _ => bail!("Unknown front matter delimiter {open:?}"),
+        }
+    }
+}
+
+/// Range of the front matter starting at `start` after blank lines, reaching from `start` to the
+/// end of the closing delimiter. A block is only front matter if it doesn't contain blank lines,
+/// so thematic breaks after a heading are left alone.
+pub(crate) fn find(content: &str, start: usize) -> Option<Range<usize>> {
+    let mut offset = start;
+    let mut lines = content[start..].split_inclusive('\n');
+    let mut line = lines.next()?;
+
+    while line.trim().is_empty() {
+        offset += line.len();
+        line = lines.next()?;
+    }
+
+    let (_, close) = DELIMITERS
+        .iter()
+        .find(|(open, _)| line.trim_end() == *open)?;
+    offset += line.len();
+
+    for line in lines {
+        offset += line.len();
+
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        if line.trim_end() == *close {
+            return Some(start..offset);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_front_matter() {
+        let content = "# One\n\n---\nname: First\ndraft: true\n---\n\nText\n";
+        let range = find(content, 6).unwrap();
+        assert_eq!(&content[range.end..], "\nText\n");
+        assert_eq!(
+            FrontMatter::parse(&content[range]).unwrap(),
+            FrontMatter {
+                name: Some("First".to_string()),
+                draft: true,
+                ..Default::default()
+            }
+        );
+
+        let content = "# One\n+++\npath = \"one.md\"\nclasses = [\"wide\"]\n+++\n";
+        assert_eq!(
+            FrontMatter::parse(&content[find(content, 6).unwrap()]).unwrap(),
+            FrontMatter {
+                path: Some(PathBuf::from("one.md")),
+                classes: vec!["wide".to_string()],
+                ..Default::default()
+            }
+        );
+
+        let content = "# One\n<!-- split\nname: First\n-->\n";
+        assert_eq!(
+            FrontMatter::parse(&content[find(content, 6).unwrap()])
+                .unwrap()
+                .name
+                .as_deref(),
+            Some("First")
+        );
+
+        assert_eq!(find("# One\n\n---\n\nText\n\n---\n", 6), None);
+        assert!(FrontMatter::parse("---\ntitle: First\n---\n").is_err());
+    }
+}
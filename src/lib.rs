@@ -1,104 +1,301 @@
 use anyhow::Error;
-use mdbook::book::{Book, BookItem, Chapter};
+use config::Config;
+use mdbook::book::{Book, BookItem, Chapter, SectionNumber};
 use mdbook::preprocess::{Preprocessor, PreprocessorContext};
-use pulldown_cmark::{Event, HeadingLevel, Tag};
-use pulldown_cmark_to_cmark::cmark;
-use sha2::Digest;
+use pulldown_cmark::HeadingLevel;
+use section::Section;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
-/// A preprocessor to split h1 headings into individual chapters.
+pub mod apply;
+pub mod config;
+mod directive;
+mod footnotes;
+mod front_matter;
+mod links;
+mod path;
+pub mod preview;
+mod print;
+mod redirect;
+mod references;
+mod section;
+
+/// A preprocessor to split headings into individual chapters.
 #[derive(Default)]
 pub struct Split;
 
-fn is_h1(event: &Event) -> bool {
-    matches!(event, Event::Start(Tag::Heading(HeadingLevel::H1, _, _)))
-}
+fn to_chapter(
+    section: &Section,
+    name: String,
+    source: &Chapter,
+    index: usize,
+    config: &Config,
+) -> Result<Chapter, Error> {
+    let front_matter = section.front_matter.clone().unwrap_or_default();
 
-fn to_cmark(events: Vec<Event>) -> Result<String, Error> {
-    let mut buf = String::new();
-    cmark(events.into_iter(), &mut buf)?;
-    Ok(buf)
-}
+    let name = front_matter.name.unwrap_or(name);
+    let (id, classes) = section.heading_attributes();
 
-fn to_chapter(events: Vec<Event>) -> Result<Chapter, Error> {
-    let name = &events
-        .windows(2)
-        .find_map(|window| is_h1(&window[0]).then_some(&window[1]))
-        .and_then(|event| match event {
-            Event::Text(text) => Some(text.to_string()),
-            _ => None,
-        })
-        .unwrap_or_default();
+    if front_matter.draft || classes.contains(&"draft") {
+        return Ok(Chapter {
+            name,
+            number: source.number.clone(),
+            parent_names: source.parent_names.clone(),
+            source_path: source.source_path.clone(),
+            ..Default::default()
+        });
+    }
+
+    let path = front_matter
+        .path
+        .unwrap_or_else(|| path::chapter_path(config.path_style, source, &name, id, index));
 
-    let content = to_cmark(events)?;
+    let mut content = section.content(&source.content);
 
-    let mut hasher = sha2::Sha256::new();
-    hasher.update(name);
-    let result = hasher.finalize();
+    if !front_matter.classes.is_empty() {
+        content = format!(
+            "<div class=\"{}\">\n\n{}\n\n</div>\n",
+            front_matter.classes.join(" "),
+            content.trim_end()
+        );
+    }
 
     Ok(Chapter {
-        name: name.to_string(),
-        path: Some(PathBuf::from(format!("{result:x}"))),
+        name,
+        path: Some(path),
         content,
+        number: source.number.clone(),
+        parent_names: source.parent_names.clone(),
+        source_path: source.source_path.clone(),
         ..Default::default()
     })
 }
 
-fn split_chapter(chapter: &Chapter) -> Result<Vec<Chapter>, Error> {
-    let mut options = pulldown_cmark::Options::empty();
-    options.insert(pulldown_cmark::Options::ENABLE_FOOTNOTES);
-    options.insert(pulldown_cmark::Options::ENABLE_SMART_PUNCTUATION);
-    options.insert(pulldown_cmark::Options::ENABLE_TABLES);
+fn split_chapter(chapter: &Chapter, config: &Config) -> Result<Vec<Chapter>, Error> {
+    // Chapters that aren't split are kept as they are, only without directives.
+    let unsplit = || Chapter {
+        content: directive::strip(&chapter.content, config),
+        ..chapter.clone()
+    };
+
+    let Some(config) = &directive::apply(&chapter.name, &chapter.content, config) else {
+        return Ok(vec![unsplit()]);
+    };
+
+    let mut sections = section::sections(&chapter.content, config);
+
+    // Without any split heading or marker there is nothing to split.
+    if let [section] = sections.as_slice() {
+        if section.level.is_none() && !section.marker {
+            return Ok(vec![unsplit()]);
+        }
+    }
+
+    section::handle_preamble(&mut sections, config.preamble);
+
+    if let Some(max_words) = config.max_words {
+        section::split_long(&mut sections, max_words);
+    }
+
+    section::merge_short(&mut sections, config.min_words);
+    footnotes::relocate(&mut sections, chapter);
+    references::propagate(&mut sections, &chapter.content, config);
+
+    let mut chapters = vec![];
+    let mut parent = chapter.name.clone();
+    let mut counter = 1;
+
+    for (index, section) in sections.iter().enumerate() {
+        let name = match section.heading_text() {
+            Some(text) => {
+                parent = text.clone();
+                counter = 1;
+                text
+            }
+            None if section.marker => {
+                counter += 1;
+                config
+                    .untitled
+                    .replace("{parent}", &parent)
+                    .replace("{n}", &counter.to_string())
+            }
+            None => chapter.name.clone(),
+        };
+
+        let new_chapter = to_chapter(section, name, chapter, index + 1, config)?;
+        chapters.push((section.level, new_chapter));
+    }
+
+    if config.nested {
+        Ok(nest(chapters, config))
+    } else {
+        Ok(chapters.into_iter().map(|(_, chapter)| chapter).collect())
+    }
+}
+
+/// Turn a flat list of split chapters into a tree, placing chapters split at deeper levels into
+/// the `sub_items` of the preceding chapter split at a shallower level.
+fn nest(sections: Vec<(Option<HeadingLevel>, Chapter)>, config: &Config) -> Vec<Chapter> {
+    fn attach(stack: &mut Vec<(usize, Chapter)>, chapters: &mut Vec<Chapter>) {
+        if let Some((_, chapter)) = stack.pop() {
+            match stack.last_mut() {
+                Some((_, parent)) => parent.sub_items.push(BookItem::Chapter(chapter)),
+                None => chapters.push(chapter),
+            }
+        }
+    }
 
-    let parser = pulldown_cmark::Parser::new_ext(&chapter.content, options);
     let mut chapters = vec![];
-    let mut events = vec![];
+    let mut stack: Vec<(usize, Chapter)> = vec![];
 
-    for event in parser {
-        let finish = is_h1(&event) && !events.is_empty();
+    for (level, chapter) in sections {
+        let depth = level.map_or(0, |level| config.level.depth(level));
 
-        if finish {
-            chapters.push(to_chapter(events)?);
-            events = vec![event];
-        } else {
-            events.push(event);
+        while stack.last().is_some_and(|(d, _)| *d >= depth) {
+            attach(&mut stack, &mut chapters);
         }
+
+        stack.push((depth, chapter));
+    }
+
+    while !stack.is_empty() {
+        attach(&mut stack, &mut chapters);
     }
 
-    if !events.is_empty() {
-        chapters.push(to_chapter(events)?);
+    for chapter in &mut chapters {
+        set_parent_names(chapter);
     }
 
-    Ok(chapters)
+    chapters
 }
 
-impl Preprocessor for Split {
-    fn name(&self) -> &str {
-        "split"
+fn set_parent_names(chapter: &mut Chapter) {
+    let mut parent_names = chapter.parent_names.clone();
+    parent_names.push(chapter.name.clone());
+
+    for item in &mut chapter.sub_items {
+        if let BookItem::Chapter(sub_chapter) = item {
+            sub_chapter.parent_names = parent_names.clone();
+            set_parent_names(sub_chapter);
+        }
     }
+}
 
-    fn run(&self, _ctx: &PreprocessorContext, book: Book) -> Result<Book, Error> {
-        let mut new_book = Book::new();
+/// Split all selected chapters in `items` and their nested sub-chapters. The sub-chapters of a
+/// split chapter are attached to the last chapter it was split into to keep the reading order.
+fn split_items(items: Vec<BookItem>, config: &Config) -> Result<Vec<BookItem>, Error> {
+    let mut new_items = vec![];
 
-        for item in book.sections {
-            match item {
-                BookItem::Chapter(ref chapter) => {
-                    for item in split_chapter(chapter)? {
-                        new_book.push_item(item);
+    for item in items {
+        match item {
+            BookItem::Chapter(mut chapter) => {
+                let sub_items = split_items(std::mem::take(&mut chapter.sub_items), config)?;
+                let mut chapters = if config.selects(&chapter) {
+                    split_chapter(&chapter, config)?
+                } else {
+                    vec![]
+                };
+
+                match chapters.last_mut() {
+                    Some(last) => {
+                        last.sub_items.extend(sub_items);
+                        set_parent_names(last);
+                    }
+                    None => {
+                        chapter.sub_items = sub_items;
+                        chapters.push(chapter);
                     }
                 }
-                BookItem::Separator => {
-                    new_book.push_item(BookItem::Separator);
-                }
-                BookItem::PartTitle(title) => {
-                    new_book.push_item(BookItem::PartTitle(title));
-                }
+
+                new_items.extend(chapters.into_iter().map(BookItem::Chapter));
             }
+            item => new_items.push(item),
+        }
+    }
+
+    Ok(new_items)
+}
+
+/// Assign consecutive section numbers to all numbered chapters below the `parent` number.
+/// Unnumbered chapters, such as prefix and suffix chapters, stay unnumbered.
+fn renumber(items: &mut [BookItem], parent: &[u32]) {
+    let mut counter = 0;
+
+    for item in items {
+        if let BookItem::Chapter(chapter) = item {
+            if chapter.number.is_some() {
+                counter += 1;
+                let mut number = parent.to_vec();
+                number.push(counter);
+                renumber(&mut chapter.sub_items, &number);
+                chapter.number = Some(SectionNumber(number));
+            }
+        }
+    }
+}
+
+/// Split all selected chapters of `book` and rewrite the links to and within them, with links to
+/// chapters ending in `extension`. With `keep_single`, chapters that didn't split into several
+/// chapters keep the path of their source file. Also returns where the content of each source file
+/// ended up.
+fn split_book(
+    book: Book,
+    config: &Config,
+    extension: &str,
+    keep_single: bool,
+) -> Result<(Book, HashMap<PathBuf, links::Source>), Error> {
+    let mut new_book = Book::new();
+    let mut items = split_items(book.sections, config)?;
+    renumber(&mut items, &[]);
+
+    if keep_single {
+        path::keep_single(&mut items);
+    }
+
+    path::disambiguate(&mut items, config.path_style);
+
+    for item in items {
+        new_book.push_item(item);
+    }
+
+    let sources = links::sources(&new_book, config);
+    links::rewrite(&mut new_book, &sources, config, extension);
+
+    Ok((new_book, sources))
+}
+
+impl Preprocessor for Split {
+    fn name(&self) -> &str {
+        "split"
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, book: Book) -> Result<Book, Error> {
+        let config = Config::from_book_config(&ctx.config, &ctx.renderer)?;
+
+        if !config.splits_for(&ctx.renderer) {
+            return Ok(book);
+        }
+
+        let (mut new_book, sources) = split_book(book, &config, "html", false)?;
+
+        if config.print_unsplit {
+            print::keep_unsplit(&mut new_book, &sources);
+        }
+
+        // Redirects only apply to the HTML renderer.
+        if let (Some(redirect_file), "html") = (&config.redirect_file, ctx.renderer.as_str()) {
+            let redirects = redirect::redirects(&new_book, &sources);
+            redirect::write(&ctx.root, redirect_file, redirects, &ctx.config)?;
+        }
+
+        if config.redirect_anchors {
+            redirect::forward_anchors(&mut new_book, &sources);
         }
 
         Ok(new_book)
     }
 
+    /// Any renderer is supported, mdBook itself honors the `renderers` setting before asking.
     fn supports_renderer(&self, _renderer: &str) -> bool {
         true
     }
@@ -107,6 +304,89 @@ impl Preprocessor for Split {
 #[cfg(test)]
 mod test {
     use super::*;
+    use pulldown_cmark::{Event, Tag};
+    use serde_json::json;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    fn run_split(split: serde_json::Value, sections: serde_json::Value) -> Book {
+        run_with_config(json!({ "preprocessor": { "split": split } }), sections)
+    }
+
+    fn run_with_config(config: serde_json::Value, sections: serde_json::Value) -> Book {
+        let mut book_config = json!({
+            "book": {
+                "authors": ["AUTHOR"],
+                "language": "en",
+                "multilingual": false,
+                "src": "src",
+                "title": "TITLE"
+            }
+        });
+        book_config
+            .as_object_mut()
+            .unwrap()
+            .extend(config.as_object().unwrap().clone());
+
+        let input = json!([
+            {
+                "root": "/path/to/book",
+                "config": book_config,
+                "renderer": "html",
+                "mdbook_version": "0.4.21"
+            },
+            {
+                "sections": sections,
+                "__non_exhaustive": null
+            }
+        ]);
+        let input = serde_json::to_vec(&input).unwrap();
+
+        let (ctx, book) = mdbook::preprocess::CmdPreprocessor::parse_input(&input[..]).unwrap();
+        Split.run(&ctx, book).unwrap()
+    }
+
+    fn chapter(name: &str, content: &str, path: &str) -> serde_json::Value {
+        json!({
+            "Chapter": {
+                "name": name,
+                "content": content,
+                "number": [1],
+                "sub_items": [],
+                "path": path,
+                "source_path": path,
+                "parent_names": []
+            }
+        })
+    }
+
+    fn names(items: &[BookItem]) -> Vec<&str> {
+        items
+            .iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => Some(chapter.name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn paths(book: &Book) -> Vec<PathBuf> {
+        book.iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => chapter.path.clone(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn contents(book: &Book) -> Vec<&str> {
+        book.iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => Some(chapter.content.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
 
     #[test]
     fn nop_preprocessor_run() {
@@ -184,4 +464,539 @@ mod test {
             _ => {}
         }
     }
+
+    #[test]
+    fn split_at_configured_levels() {
+        let content = "# Intro\n\n## First\n\ntext\n\n## Second\n\ntext\n";
+
+        let book = run_split(
+            json!({ "level": 2 }),
+            json!([chapter("Intro", content, "intro.md")]),
+        );
+        assert_eq!(names(&book.sections), ["Intro", "First", "Second"]);
+
+        let book = run_split(
+            json!({ "level": [1, 2] }),
+            json!([chapter("Intro", content, "intro.md")]),
+        );
+        assert_eq!(names(&book.sections), ["Intro", "First", "Second"]);
+    }
+
+    #[test]
+    fn reject_invalid_level() {
+        let config: Result<config::Levels, _> = serde_json::from_value(json!(7));
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn nest_deeper_levels() {
+        let content = "# One\n\n## One.One\n\n## One.Two\n\n# Two\n\n## Two.One\n";
+        let book = run_split(
+            json!({ "level": [1, 2], "nested": true }),
+            json!([chapter("One", content, "one.md")]),
+        );
+        assert_eq!(names(&book.sections), ["One", "Two"]);
+
+        let BookItem::Chapter(one) = &book.sections[0] else {
+            panic!("expected chapter");
+        };
+        assert_eq!(names(&one.sub_items), ["One.One", "One.Two"]);
+
+        let BookItem::Chapter(two) = &book.sections[1] else {
+            panic!("expected chapter");
+        };
+        assert_eq!(names(&two.sub_items), ["Two.One"]);
+
+        let BookItem::Chapter(two_one) = &two.sub_items[0] else {
+            panic!("expected chapter");
+        };
+        assert_eq!(two_one.parent_names, ["Two"]);
+    }
+
+    #[test]
+    fn keep_nested_chapters() {
+        let mut parent = chapter("Parent", "# One\n\n# Two\n", "parent.md");
+        let mut child = chapter("Child", "# Three\n\n# Four\n", "child.md");
+        child["Chapter"]["parent_names"] = json!(["Parent"]);
+        parent["Chapter"]["sub_items"] = json!([child]);
+
+        let book = run_split(json!({}), json!([parent]));
+        assert_eq!(names(&book.sections), ["One", "Two"]);
+
+        let BookItem::Chapter(two) = &book.sections[1] else {
+            panic!("expected chapter");
+        };
+        assert_eq!(names(&two.sub_items), ["Three", "Four"]);
+
+        let BookItem::Chapter(four) = &two.sub_items[1] else {
+            panic!("expected chapter");
+        };
+        assert_eq!(four.parent_names, ["Two"]);
+    }
+
+    #[test]
+    fn renumber_split_chapters() {
+        let mut second = chapter("Three", "# Three\n\n## Three.One\n", "three.md");
+        second["Chapter"]["number"] = json!([2]);
+
+        let book = run_split(
+            json!({ "level": [1, 2], "nested": true }),
+            json!([chapter("One", "# One\n\n# Two\n", "one.md"), second]),
+        );
+
+        let numbers = book
+            .iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => chapter.number.as_ref().map(|n| n.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, ["1.", "2.", "3.", "3.1."]);
+
+        let BookItem::Chapter(two) = &book.sections[1] else {
+            panic!("expected chapter");
+        };
+        assert_eq!(two.source_path.as_ref().unwrap().to_str(), Some("one.md"));
+    }
+
+    #[test]
+    fn slug_and_source_index_paths() {
+        let content = "# Getting Started\n\n# Next Steps\n";
+
+        let book = run_split(
+            json!({ "path-style": "slug" }),
+            json!([chapter("Intro", content, "guide/intro.md")]),
+        );
+        assert_eq!(
+            paths(&book),
+            [
+                PathBuf::from("guide/intro/getting-started.md"),
+                PathBuf::from("guide/intro/next-steps.md")
+            ]
+        );
+
+        let book = run_split(
+            json!({ "path-style": "source-index" }),
+            json!([chapter("Intro", content, "guide/intro.md")]),
+        );
+        assert_eq!(
+            paths(&book),
+            [
+                PathBuf::from("guide/intro/1.md"),
+                PathBuf::from("guide/intro/2.md")
+            ]
+        );
+    }
+
+    #[test]
+    fn disambiguate_duplicate_paths() {
+        let content = "# Summary\n\n# Summary\n";
+        let book = run_split(
+            json!({ "path-style": "slug" }),
+            json!([
+                chapter("One", content, "one.md"),
+                chapter("Two", "# Summary\n", "two.md")
+            ]),
+        );
+        assert_eq!(
+            paths(&book),
+            [
+                PathBuf::from("one/summary.md"),
+                PathBuf::from("one/summary-2.md"),
+                PathBuf::from("two/summary.md")
+            ]
+        );
+
+        let book = run_split(
+            json!({}),
+            json!([
+                chapter("One", "# Summary\n", "one.md"),
+                chapter("Two", "# Summary\n", "two.md")
+            ]),
+        );
+        let unique = paths(&book).into_iter().collect::<HashSet<_>>();
+        assert_eq!(unique.len(), 2);
+
+        let book = run_split(
+            json!({ "path-style": "slug", "include": ["guide.md"] }),
+            json!([
+                chapter("Guide", "# Setup\n\n# Usage\n", "guide.md"),
+                chapter("Setup", "# Setup\n", "guide/setup.md")
+            ]),
+        );
+        assert_eq!(
+            paths(&book),
+            [
+                PathBuf::from("guide/setup-2.md"),
+                PathBuf::from("guide/usage.md"),
+                PathBuf::from("guide/setup.md")
+            ]
+        );
+    }
+
+    #[test]
+    fn full_heading_text_as_name() {
+        let content = "# **Bold** intro\n\n# The `foo` API\n\n# [Link](x) and _more_\n";
+        let book = run_split(json!({}), json!([chapter("Intro", content, "intro.md")]));
+        assert_eq!(
+            names(&book.sections),
+            ["Bold intro", "The foo API", "Link and more"]
+        );
+    }
+
+    #[test]
+    fn keep_source_verbatim() {
+        let first = "# \"Quotes\" -- and *emphasis*\n\n* item\n+ other\n\n<div>\nhtml\n</div>\n\n";
+        let second = "# Table\n\n| a | b |\n|:--|--:|\n| 1 | 2 |\n";
+        let content = format!("{first}{second}");
+        let book = run_split(json!({}), json!([chapter("Intro", &content, "intro.md")]));
+
+        assert_eq!(contents(&book), [first, second]);
+    }
+
+    #[test]
+    fn parse_with_book_markdown_options() {
+        let content = "# \"Intro\" {#intro}\n\n# ~~Old~~ New\n";
+
+        let book = run_split(json!({}), json!([chapter("Intro", content, "intro.md")]));
+        assert_eq!(names(&book.sections), ["\"Intro\"", "Old New"]);
+
+        let book = run_with_config(
+            json!({ "output": { "html": { "smart-punctuation": true } } }),
+            json!([chapter("Intro", content, "intro.md")]),
+        );
+        assert_eq!(names(&book.sections), ["“Intro”", "Old New"]);
+
+        let book = run_split(
+            json!({ "markdown": { "heading-attributes": false } }),
+            json!([chapter("Intro", content, "intro.md")]),
+        );
+        assert_eq!(names(&book.sections), ["\"Intro\" {#intro}", "Old New"]);
+    }
+
+    #[test]
+    fn preamble_policies() {
+        let content = "Preamble\n\n# One\n\n# Two\n";
+
+        let book = run_split(json!({}), json!([chapter("Intro", content, "intro.md")]));
+        assert_eq!(names(&book.sections), ["Intro", "One", "Two"]);
+
+        let book = run_split(
+            json!({ "preamble": "attach" }),
+            json!([chapter("Intro", content, "intro.md")]),
+        );
+        assert_eq!(names(&book.sections), ["One", "Two"]);
+        assert_eq!(contents(&book), ["Preamble\n\n# One\n\n", "# Two\n"]);
+
+        let book = run_split(
+            json!({ "preamble": "drop" }),
+            json!([chapter("Intro", content, "intro.md")]),
+        );
+        assert_eq!(contents(&book), ["# One\n\n", "# Two\n"]);
+
+        let book = run_split(
+            json!({ "preamble": "drop" }),
+            json!([chapter("Intro", "No headings\n", "intro.md")]),
+        );
+        assert_eq!(names(&book.sections), ["Intro"]);
+        assert_eq!(paths(&book), [PathBuf::from("intro.md")]);
+
+        let book = run_split(
+            json!({ "path-style": "slug" }),
+            json!([chapter("Intro", "No headings\n", "intro.md")]),
+        );
+        assert_eq!(paths(&book), [PathBuf::from("intro.md")]);
+    }
+
+    #[test]
+    fn relocate_footnotes() {
+        let content = "# One\n\nFirst[^a][^d].\n\n# Two\n\nSecond[^a][^b].\n\n\
+                       [^a]: A.\n[^b]: B.\n[^c]: C.\n[^d]: D.\n";
+        let book = run_split(json!({}), json!([chapter("Intro", content, "intro.md")]));
+        assert_eq!(
+            contents(&book),
+            [
+                "# One\n\nFirst[^a][^d].\n\n[^a]: A.\n\n[^d]: D.\n",
+                "# Two\n\nSecond[^a][^b].\n\n[^a]: A.\n[^b]: B.\n[^c]: C.\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn propagate_reference_definitions() {
+        let content = "# One\n\nSee [docs][ref] and [other].\n\n# Two\n\n\
+                       [ref]: https://example.com\n\
+                       [other]: https://example.org \"Other\"\n\
+                       [unused]: https://example.net\n";
+        let book = run_split(json!({}), json!([chapter("Intro", content, "intro.md")]));
+        assert_eq!(
+            contents(&book)[0],
+            "# One\n\nSee [docs][ref] and [other].\n\n\
+             [ref]: https://example.com\n\n\
+             [other]: https://example.org \"Other\"\n"
+        );
+
+        let content = "# A\n\nSee [x] and ![img][Y  Label].\n\n# B\n\n\
+                       [x]: https://example.com\n\
+                       [y label]: https://example.com\n\
+                       [z]: https://example.com\n";
+        let book = run_split(json!({}), json!([chapter("Ch", content, "ch.md")]));
+        assert_eq!(
+            contents(&book)[0],
+            "# A\n\nSee [x] and ![img][Y  Label].\n\n\
+             [x]: https://example.com\n\n\
+             [y label]: https://example.com\n"
+        );
+
+        // Footnote definitions moved along with their references bring the definitions they use.
+        let content = "# One\n\nSee[^1]\n\n# Two\n\n[^1]: note [ref]\n\n[ref]: http://x\n";
+        let book = run_split(json!({}), json!([chapter("Ch", content, "ch.md")]));
+        assert_eq!(
+            contents(&book)[0],
+            "# One\n\nSee[^1]\n\n[^1]: note [ref]\n\n[ref]: http://x\n"
+        );
+    }
+
+    #[test]
+    fn rewrite_anchor_links() {
+        let intro = "# One\n\nSee [below](#two-1).\n\n# Two\n\n## Two\n\n[up]: #one\n\nGo [up].\n";
+        let other = "# Other\n\nSee [intro](guide/intro.md) and [two](./guide/intro.md#two-1).\n\n\
+                     ![image](images/one.png)\n";
+        let book = run_split(
+            json!({ "path-style": "slug" }),
+            json!([
+                chapter("Intro", intro, "guide/intro.md"),
+                chapter("Other", other, "other.md")
+            ]),
+        );
+        assert_eq!(
+            contents(&book),
+            [
+                "# One\n\nSee [below](two.html#two-1).\n\n",
+                "# Two\n\n## Two\n\n[up]: one.html#one\n\nGo [up].\n",
+                "# Other\n\nSee [intro](../guide/intro/one.html) and \
+                 [two](../guide/intro/two.html#two-1).\n\n\
+                 ![image](../images/one.png)\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_link_titles_repeating_destinations() {
+        let content =
+            "# One\n\n[x](a.md#two \"a.md\") [![y](a.png)](a.md#two \"a.md#two\") [z]\n\n\
+                       [z]: a.md#two \"a.md#two\"\n\n# Two\n";
+        let book = run_split(
+            json!({ "path-style": "slug" }),
+            json!([chapter("A", content, "a.md")]),
+        );
+        assert_eq!(
+            contents(&book)[0],
+            "# One\n\n[x](two.html#two \"a.md\") [![y](../a.png)](two.html#two \"a.md#two\") [z]\n\n\
+             [z]: two.html#two \"a.md#two\"\n\n"
+        );
+    }
+
+    #[test]
+    fn forward_moved_anchors() {
+        let content = "# One\n\n# Two\n";
+        let book = run_split(
+            json!({ "path-style": "slug", "redirect-anchors": true }),
+            json!([chapter("Intro", content, "intro.md")]),
+        );
+        let contents = contents(&book);
+        assert!(contents[0].contains(r##"var anchors = {"two":"two.html#two"};"##));
+        assert_eq!(contents[1], "# Two\n");
+    }
+
+    #[test]
+    fn include_and_exclude_chapters() {
+        let content = "# One\n\n# Two\n";
+        let book = run_split(
+            json!({ "include": ["slides/**"], "exclude": ["slides/skip/*.md"] }),
+            json!([
+                chapter("Prose", content, "prose.md"),
+                chapter("Deck", content, "slides/deck/intro.md"),
+                chapter("Skipped", content, "slides/skip/intro.md")
+            ]),
+        );
+        assert_eq!(names(&book.sections), ["Prose", "One", "Two", "Skipped"]);
+    }
+
+    #[test]
+    fn in_file_directives() {
+        let content =
+            "<!-- split: level=2 -->\n# Intro\n\n## One\n\n<!-- nosplit -->\n## Two\n\n## Three\n";
+        let book = run_split(
+            json!({ "preamble": "attach" }),
+            json!([chapter("Intro", content, "intro.md")]),
+        );
+        assert_eq!(names(&book.sections), ["One", "Three"]);
+        assert_eq!(
+            contents(&book),
+            ["# Intro\n\n## One\n\n## Two\n\n", "## Three\n"]
+        );
+
+        let content = "<!-- split: off -->\n# One\n\n# Two\n";
+        let book = run_split(json!({}), json!([chapter("Intro", content, "intro.md")]));
+        assert_eq!(names(&book.sections), ["Intro"]);
+        assert_eq!(paths(&book), [PathBuf::from("intro.md")]);
+        assert_eq!(contents(&book), ["# One\n\n# Two\n"]);
+
+        // Chapter-wide directives only count at the top of the file.
+        let content = "# One\n\n<!-- split: off -->\n# Two\n";
+        let book = run_split(json!({}), json!([chapter("Intro", content, "intro.md")]));
+        assert_eq!(names(&book.sections), ["One", "Two"]);
+        assert_eq!(contents(&book), ["# One\n\n", "# Two\n"]);
+
+        // Directives nested in other blocks stay content, like they do in split chapters.
+        let content = "<!-- split: off -->\n> <!-- nosplit -->\n> # One\n";
+        let book = run_split(json!({}), json!([chapter("Intro", content, "intro.md")]));
+        assert_eq!(contents(&book), ["> <!-- nosplit -->\n> # One\n"]);
+    }
+
+    #[test]
+    fn split_at_markers() {
+        let content = "# Intro\n\nText\n\n---\n\n![image](a.png)\n\n<!-- slide -->\n\nMore\n\n\
+                       > quoted\n> \n> ---\n";
+        let book = run_split(
+            json!({ "split-on-rule": true, "marker": "<!-- slide -->" }),
+            json!([chapter("Intro", content, "intro.md")]),
+        );
+        assert_eq!(names(&book.sections), ["Intro", "Intro (2)", "Intro (3)"]);
+        assert_eq!(
+            contents(&book),
+            [
+                "# Intro\n\nText\n\n",
+                "\n![image](a.png)\n\n",
+                "\nMore\n\n> quoted\n> \n> ---\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn front_matter_per_section() {
+        let content = "# One\n\n---\nname: First\npath: first.md\nclasses: [wide, dark]\n---\n\n\
+                       Text\n\n# Two\n+++\ndraft = true\n+++\n\n# Three\n";
+        let book = run_split(
+            json!({ "front-matter": true }),
+            json!([chapter("One", content, "one.md")]),
+        );
+        assert_eq!(names(&book.sections), ["First", "Two", "Three"]);
+        assert_eq!(paths(&book)[0], PathBuf::from("first.md"));
+        assert_eq!(paths(&book).len(), 2);
+        assert_eq!(
+            contents(&book),
+            [
+                "<div class=\"wide dark\">\n\n# One\n\nText\n\n</div>\n",
+                "",
+                "# Three\n"
+            ]
+        );
+
+        let content = "# One\n---\nfoo: bar\n---\n";
+        let book = run_split(json!({}), json!([chapter("One", content, "one.md")]));
+        assert_eq!(contents(&book), [content]);
+    }
+
+    #[test]
+    fn keep_invalid_front_matter_as_content() {
+        for content in [
+            "# Title\n\n---\nSome text\n---\n\n# Next\n",
+            "# Title\n---\ntitle: x\n---\n\n# Next\n",
+        ] {
+            let book = run_split(
+                json!({ "front-matter": true }),
+                json!([chapter("Ch", content, "ch.md")]),
+            );
+            assert_eq!(names(&book.sections), ["Title", "Next"]);
+            assert_eq!(contents(&book)[1], "# Next\n");
+        }
+    }
+
+    #[test]
+    fn heading_attributes() {
+        let content = "# One {#first .wide}\n\n# Two {.nosplit}\n\n# Three {.draft}\n\n# Four\n";
+        let book = run_split(
+            json!({ "path-style": "slug" }),
+            json!([chapter("One", content, "one.md")]),
+        );
+        assert_eq!(names(&book.sections), ["One", "Three", "Four"]);
+        assert_eq!(
+            paths(&book),
+            [PathBuf::from("one/first.md"), PathBuf::from("one/four.md")]
+        );
+    }
+
+    #[test]
+    fn split_by_word_count() {
+        let content = "# One\n\nOne two three four.\n\n## A\n\nFive six.\n\n## B\n\nSeven.\n\n\
+                       # Two\n\nEight.\n\n# Three\n\nNine ten eleven.\n";
+        let book = run_split(
+            json!({ "min-words": 3, "max-words": 6 }),
+            json!([chapter("One", content, "one.md")]),
+        );
+        assert_eq!(names(&book.sections), ["One", "A", "Three"]);
+        assert_eq!(
+            contents(&book)[1],
+            "## A\n\nFive six.\n\n## B\n\nSeven.\n\n# Two\n\nEight.\n\n"
+        );
+    }
+
+    #[test]
+    fn renderer_specific_settings() {
+        let content = "# One\n\n## Two\n";
+        let book = run_split(
+            json!({ "renderers": ["epub"] }),
+            json!([chapter("One", content, "one.md")]),
+        );
+        assert_eq!(names(&book.sections), ["One"]);
+
+        let book = run_split(
+            json!({
+                "renderers": ["html"],
+                "renderer": {
+                    "html": { "level": 2, "markdown": { "tables": false } },
+                    "epub": { "level": 3 }
+                }
+            }),
+            json!([chapter("One", content, "one.md")]),
+        );
+        assert_eq!(names(&book.sections), ["One", "Two"]);
+    }
+
+    #[test]
+    fn keep_unsplit_chapter_for_printing() {
+        let book = run_split(
+            json!({ "path-style": "slug", "print-unsplit": true }),
+            json!([
+                chapter("One", "# One\n\nSee [two](#two).\n\n# Two\n", "one.md"),
+                chapter("Other", "# Other\n", "other.md")
+            ]),
+        );
+        let contents = contents(&book);
+        assert!(contents[0].starts_with("<style>"));
+        assert!(contents[0].ends_with("\n\n# One\n\nSee [two](two.html#two).\n\n"));
+        assert_eq!(
+            contents[1],
+            "<div class=\"split-continued\"></div>\n\n# Two\n"
+        );
+
+        // mdBook numbers repeated headings on a page, like `one-1` for a second "One", so each
+        // heading must appear once for the print page to keep the anchors of the split chapters.
+        let mut headings = vec![];
+        for content in &contents {
+            let mut heading = false;
+            for event in pulldown_cmark::Parser::new(content) {
+                match event {
+                    Event::Start(Tag::Heading(..)) => heading = true,
+                    Event::End(Tag::Heading(..)) => heading = false,
+                    Event::Text(text) if heading => headings.push(text.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(headings, ["One", "Two", "Other"]);
+        assert_eq!(contents[2], "# Other\n");
+    }
 }
@@ -0,0 +1,288 @@
+use crate::config::Config;
+use crate::section::heading_text;
+use mdbook::book::{Book, BookItem, Chapter};
+use mdbook::utils::unique_id_from_content;
+use pulldown_cmark::{Event, LinkType, Tag};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+/// Where the content of a source file ended up after splitting.
+pub(crate) struct Source {
+    /// Path of the first chapter taken from the source file.
+    pub first: PathBuf,
+    /// Maps anchors of the source file to the chapter path and anchor they have now.
+    pub anchors: HashMap<String, (PathBuf, String)>,
+}
+
+/// Anchors of all headings in `content`, generated the same way mdBook does.
+fn anchors(content: &str, config: &Config, counter: &mut HashMap<String, usize>) -> Vec<String> {
+    let mut events = pulldown_cmark::Parser::new_ext(content, config.markdown.parser_options());
+    let mut anchors = vec![];
+
+    while let Some(event) = events.next() {
+        if let Event::Start(Tag::Heading(_, id, _)) = event {
+            let text = heading_text(&mut events);
+
+            anchors.push(match id {
+                Some(id) => id.to_string(),
+                None => unique_id_from_content(&text, counter),
+            });
+        }
+    }
+
+    anchors
+}
+
+/// Locations of the content of all source files of the chapters in `book`.
+pub(crate) fn sources(book: &Book, config: &Config) -> HashMap<PathBuf, Source> {
+    let mut sources = HashMap::new();
+    let mut counters = HashMap::new();
+
+    for item in book.iter() {
+        let BookItem::Chapter(chapter) = item else {
+            continue;
+        };
+        let (Some(path), Some(source_path)) = (&chapter.path, &chapter.source_path) else {
+            continue;
+        };
+
+        let source = sources
+            .entry(source_path.clone())
+            .or_insert_with(|| Source {
+                first: path.clone(),
+                anchors: HashMap::new(),
+            });
+
+        let counter = counters.entry(source_path.clone()).or_default();
+        let old = anchors(&chapter.content, config, counter);
+        let new = anchors(&chapter.content, config, &mut HashMap::new());
+
+        for (old, new) in old.into_iter().zip(new) {
+            source.anchors.entry(old).or_insert((path.clone(), new));
+        }
+    }
+
+    sources
+}
+
+/// `path` with `.` and `..` components resolved lexically.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Path of `to` relative to the directory `from`.
+fn relative(from: &Path, to: &Path) -> PathBuf {
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut relative = PathBuf::new();
+
+    for _ in from.components().skip(common) {
+        relative.push("..");
+    }
+
+    relative.extend(to.components().skip(common));
+    relative
+}
+
+/// URL of `to` relative to the directory `from`.
+pub(crate) fn relative_url(from: &Path, to: &Path) -> String {
+    relative(from, to).to_string_lossy().replace('\\', "/")
+}
+
+/// New destination of the link to `dest` found in `chapter` or `None` if it stays as it is, with
+/// links to chapters ending in `extension`.
+fn resolve(
+    dest: &str,
+    chapter: &Chapter,
+    sources: &HashMap<PathBuf, Source>,
+    extension: &str,
+) -> Option<String> {
+    let path = chapter.path.as_ref()?;
+    let source_path = chapter.source_path.as_ref()?;
+
+    if dest.is_empty() || dest.contains("://") || dest.starts_with("mailto:") {
+        return None;
+    }
+
+    let (file, fragment) = match dest.split_once('#') {
+        Some((file, fragment)) => (file, Some(fragment)),
+        None => (dest, None),
+    };
+
+    let source_dir = source_path.parent().unwrap_or(Path::new(""));
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let target_source = if file.is_empty() {
+        source_path.clone()
+    } else if file.starts_with('/') {
+        return None;
+    } else {
+        normalize(&source_dir.join(file))
+    };
+
+    let Some(source) = sources.get(&target_source) else {
+        // Not a chapter, only keep the link working if the chapter moved to another directory.
+        if dir == source_dir {
+            return None;
+        }
+
+        let mut new_dest = relative_url(dir, &target_source);
+
+        if let Some(fragment) = fragment {
+            new_dest.push('#');
+            new_dest.push_str(fragment);
+        }
+
+        return Some(new_dest);
+    };
+
+    let (target, anchor) = match fragment {
+        Some(fragment) => match source.anchors.get(fragment) {
+            Some((target, anchor)) => (target, Some(anchor.as_str())),
+            None => {
+                eprintln!(
+                    "Warning: Unable to resolve link to {dest} in chapter \"{}\"",
+                    chapter.name
+                );
+                return None;
+            }
+        },
+        None => (&source.first, None),
+    };
+
+    let unchanged = if file.is_empty() {
+        target == path
+    } else {
+        *target == target_source && dir == source_dir
+    };
+
+    if unchanged && anchor == fragment {
+        return None;
+    }
+
+    let mut new_dest = if target == path {
+        String::new()
+    } else {
+        relative_url(dir, &target.with_extension(extension))
+    };
+
+    if let Some(anchor) = anchor {
+        new_dest.push('#');
+        new_dest.push_str(anchor);
+    }
+
+    Some(new_dest)
+}
+
+/// Rewrite the links of a single `chapter`, see [`rewrite`].
+pub(crate) fn rewrite_chapter(
+    chapter: &mut Chapter,
+    sources: &HashMap<PathBuf, Source>,
+    config: &Config,
+    extension: &str,
+) {
+    let parser =
+        pulldown_cmark::Parser::new_ext(&chapter.content, config.markdown.parser_options());
+
+    // Reference links are rewritten at their definition, inline links at the link itself. Each
+    // link comes with the part of the content its destination is in, after the definition's label
+    // or the link's text, so titles repeating the destination are left alone.
+    let mut links: Vec<(Range<usize>, &str, String)> = parser
+        .reference_definitions()
+        .iter()
+        .map(|(_, definition)| (definition.span.clone(), "]:", definition.dest.to_string()))
+        .collect();
+
+    // Open links and images with the end of their text so far and their inline destination.
+    let mut open: Vec<(usize, Option<String>)> = vec![];
+
+    for (event, range) in parser.into_offset_iter() {
+        if let Event::End(Tag::Link(..) | Tag::Image(..)) = event {
+            if let Some((text_end, Some(dest))) = open.pop() {
+                links.push((text_end..range.end, "](", dest));
+            }
+        }
+
+        // Everything inside a link up to its destination, nested images included, is its text.
+        if let Some((text_end, _)) = open.last_mut() {
+            *text_end = (*text_end).max(range.end);
+        }
+
+        if let Event::Start(Tag::Link(link_type, dest, _) | Tag::Image(link_type, dest, _)) = event
+        {
+            let dest = (link_type == LinkType::Inline).then(|| dest.to_string());
+            open.push((range.start, dest));
+        }
+    }
+
+    let mut replacements = links
+        .into_iter()
+        .filter_map(|(range, delimiter, dest)| {
+            let new_dest = resolve(&dest, chapter, sources, extension)?;
+            let content = &chapter.content[range.clone()];
+            let after = content.find(delimiter)? + delimiter.len();
+            let start = range.start + after + content[after..].find(&dest)?;
+            Some((start..start + dest.len(), new_dest))
+        })
+        .collect::<Vec<_>>();
+
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    for (range, new_dest) in replacements.into_iter().rev() {
+        chapter.content.replace_range(range, &new_dest);
+    }
+}
+
+/// Rewrite links to headings of split source files so they point at the chapters the headings
+/// ended up in, and relative links of chapters that moved to another directory. Links to chapters
+/// end in `extension`, i.e. `html` for rendered chapters and `md` for source files.
+pub(crate) fn rewrite(
+    book: &mut Book,
+    sources: &HashMap<PathBuf, Source>,
+    config: &Config,
+    extension: &str,
+) {
+    book.for_each_mut(|item| {
+        if let BookItem::Chapter(chapter) = item {
+            rewrite_chapter(chapter, sources, config, extension);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(
+            relative(Path::new("intro"), Path::new("intro/two.html")),
+            PathBuf::from("two.html")
+        );
+        assert_eq!(
+            relative(Path::new("guide/a"), Path::new("intro/two.html")),
+            PathBuf::from("../../intro/two.html")
+        );
+        assert_eq!(
+            normalize(Path::new("guide/./../intro.md")),
+            PathBuf::from("intro.md")
+        );
+    }
+}
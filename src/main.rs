@@ -1,14 +1,93 @@
-use clap::{Arg, ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use mdbook::errors::Error;
 use mdbook::preprocess::{CmdPreprocessor, Preprocessor};
+use mdbook_split::preview::Preview;
 use mdbook_split::Split;
 use semver::{Version, VersionReq};
 use std::io;
+use std::path::Path;
 use std::process;
 
+const CONFIG_HELP: &str = "\
+Configuration, in the [preprocessor.split] table of book.toml, with defaults:
+  renderers                     Only split chapters for these renderers, unset
+  level = 1                     Heading level or levels to split at
+  nested = false                Nest chapters split at deeper levels
+  path-style = \"hash\"           \"hash\", \"slug\" or \"source-index\"
+  split-on-rule = false         Also split at thematic breaks
+  marker                        HTML comment to split at, unset
+  untitled = \"{parent} ({n})\"   Name of chapters split at a break or marker
+  min-words = 0                 Merge chapters with fewer words
+  max-words                     Split chapters with more words deeper, unset
+  front-matter = false          Read front matter after split headings
+  preamble = \"intro\"            \"intro\", \"attach\" or \"drop\"
+  redirect-file                 Write redirects for split files here, unset
+  redirect-anchors = false      Forward anchors of split files
+  print-unsplit = false         Print split files unsplit
+  include = []                  Only split files matching these globs
+  exclude = []                  Never split files matching these globs
+
+Markdown extensions are set in [preprocessor.split.markdown] and settings for
+a single renderer in [preprocessor.split.renderer.<name>]. See the README for
+details.";
+
 fn make_app() -> Command {
-    Command::new("nop-preprocessor")
-        .about("A mdbook preprocessor which does precisely nothing")
+    Command::new("mdbook-split")
+        .about("A mdBook preprocessor that splits chapters at headings into individual chapters")
+        // Part of the template rather than after_help, which would turn the `{n}` of the
+        // untitled template into a line break.
+        .help_template(format!(
+            "{{before-help}}{{about-with-newline}}\n{{usage-heading}} {{usage}}\n\n\
+             {{all-args}}{{after-help}}\n\n{CONFIG_HELP}"
+        ))
+        .version(env!("CARGO_PKG_VERSION"))
+        .long_version(format!(
+            "{} (mdbook {})",
+            env!("CARGO_PKG_VERSION"),
+            mdbook::MDBOOK_VERSION
+        ))
+        .subcommand(
+            Command::new("apply")
+                .arg(
+                    Arg::new("root")
+                        .default_value(".")
+                        .help("Root directory of the book"),
+                )
+                .about(
+                    "Split the source files of a book on disk and list them in SUMMARY.md, so \
+                     the book no longer needs the preprocessor",
+                ),
+        )
+        .subcommand(
+            Command::new("preview")
+                .arg(
+                    Arg::new("root")
+                        .default_value(".")
+                        .help("Root directory of the book"),
+                )
+                .arg(
+                    Arg::new("stdin")
+                        .long("stdin")
+                        .action(ArgAction::SetTrue)
+                        .help("Read a preprocessor JSON payload from stdin instead of the book"),
+                )
+                .arg(
+                    Arg::new("renderer")
+                        .long("renderer")
+                        .default_value("html")
+                        .help("Renderer to preview the book for, unless read from stdin"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the chapters as JSON"),
+                )
+                .about(
+                    "Print the chapters of a book after splitting with their paths, numbers and \
+                     word counts",
+                ),
+        )
         .subcommand(
             Command::new("supports")
                 .arg(Arg::new("renderer").required(true))
@@ -19,9 +98,21 @@ fn make_app() -> Command {
 fn main() {
     let matches = make_app().get_matches();
 
-    let preprocessor = Split {};
+    let preprocessor = Split;
 
-    if let Some(sub_args) = matches.subcommand_matches("supports") {
+    if let Some(sub_args) = matches.subcommand_matches("apply") {
+        let root = sub_args.get_one::<String>("root").expect("Default value");
+
+        if let Err(e) = mdbook_split::apply::apply(Path::new(root)) {
+            eprintln!("{e:#}");
+            process::exit(1);
+        }
+    } else if let Some(sub_args) = matches.subcommand_matches("preview") {
+        if let Err(e) = handle_preview(sub_args) {
+            eprintln!("{e:#}");
+            process::exit(1);
+        }
+    } else if let Some(sub_args) = matches.subcommand_matches("supports") {
         handle_supports(&preprocessor, sub_args);
     } else if let Err(e) = handle_preprocessing(&preprocessor) {
         eprintln!("{e}");
@@ -51,6 +142,26 @@ fn handle_preprocessing(pre: &dyn Preprocessor) -> Result<(), Error> {
     Ok(())
 }
 
+fn handle_preview(sub_args: &ArgMatches) -> Result<(), Error> {
+    let preview = if sub_args.get_flag("stdin") {
+        Preview::from_json(io::stdin())?
+    } else {
+        let root = sub_args.get_one::<String>("root").expect("Default value");
+        let renderer = sub_args
+            .get_one::<String>("renderer")
+            .expect("Default value");
+        Preview::load(Path::new(root), renderer)?
+    };
+
+    if sub_args.get_flag("json") {
+        println!("{}", preview.to_json()?);
+    } else {
+        print!("{}", preview.to_text());
+    }
+
+    Ok(())
+}
+
 fn handle_supports(pre: &dyn Preprocessor, sub_args: &ArgMatches) -> ! {
     let renderer = sub_args
         .get_one::<String>("renderer")
@@ -0,0 +1,183 @@
+use crate::config::PathStyle;
+use mdbook::book::{BookItem, Chapter};
+use sha2::Digest;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Turn `text` into a lowercase, dash-separated string suitable for URLs.
+pub(crate) fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.truncate(slug.trim_end_matches('-').len());
+    slug
+}
+
+fn hash(text: &str) -> String {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(text);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Directory named after the source chapter's file, i.e. `intro` for `intro.md`.
+fn source_dir(source: &Chapter) -> PathBuf {
+    source
+        .path
+        .as_ref()
+        .map(|path| path.with_extension(""))
+        .unwrap_or_default()
+}
+
+/// Output path of the `index`th chapter called `name` split off from `source`, named after the
+/// explicit `id` of its heading if it has one.
+pub(crate) fn chapter_path(
+    style: PathStyle,
+    source: &Chapter,
+    name: &str,
+    id: Option<&str>,
+    index: usize,
+) -> PathBuf {
+    if let Some(id) = id {
+        return source_dir(source).join(format!("{id}.md"));
+    }
+
+    match style {
+        PathStyle::Hash => PathBuf::from(hash(name)),
+        PathStyle::Slug => {
+            let slug = slugify(name);
+
+            if slug.is_empty() {
+                source_dir(source).join(format!("{index}.md"))
+            } else {
+                source_dir(source).join(format!("{slug}.md"))
+            }
+        }
+        PathStyle::SourceIndex => source_dir(source).join(format!("{index}.md")),
+    }
+}
+
+/// Alternative for a `path` that is already taken, using the chapter's source path and the
+/// occurrence `counter` to tell it apart.
+fn alternative_path(style: PathStyle, path: &Path, chapter: &Chapter, counter: usize) -> PathBuf {
+    match style {
+        PathStyle::Hash => {
+            let source = chapter.source_path.as_deref().unwrap_or(Path::new(""));
+            PathBuf::from(hash(&format!(
+                "{}:{}:{counter}",
+                source.display(),
+                chapter.name
+            )))
+        }
+        PathStyle::Slug | PathStyle::SourceIndex => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            path.with_file_name(format!("{stem}-{counter}.md"))
+        }
+    }
+}
+
+/// Paths of the chapters in `items` that still have the path of their source file.
+fn unchanged_paths(items: &[BookItem], paths: &mut HashSet<PathBuf>) {
+    for item in items {
+        if let BookItem::Chapter(chapter) = item {
+            if let Some(path) = &chapter.path {
+                if chapter.source_path.as_ref() == Some(path) {
+                    paths.insert(path.clone());
+                }
+            }
+
+            unchanged_paths(&chapter.sub_items, paths);
+        }
+    }
+}
+
+/// Give chapters that are the only chapter taken from their source file the path of the source
+/// file back, so only source files that split into several chapters move.
+pub(crate) fn keep_single(items: &mut [BookItem]) {
+    fn count(items: &[BookItem], counts: &mut HashMap<PathBuf, usize>) {
+        for item in items {
+            if let BookItem::Chapter(chapter) = item {
+                if let Some(source_path) = &chapter.source_path {
+                    *counts.entry(source_path.clone()).or_default() += 1;
+                }
+
+                count(&chapter.sub_items, counts);
+            }
+        }
+    }
+
+    fn restore(items: &mut [BookItem], counts: &HashMap<PathBuf, usize>) {
+        for item in items {
+            if let BookItem::Chapter(chapter) = item {
+                if let (Some(_), Some(source_path)) = (&chapter.path, &chapter.source_path) {
+                    if counts.get(source_path) == Some(&1) {
+                        chapter.path = Some(source_path.clone());
+                    }
+                }
+
+                restore(&mut chapter.sub_items, counts);
+            }
+        }
+    }
+
+    let mut counts = HashMap::new();
+    count(items, &mut counts);
+    restore(items, &counts);
+}
+
+/// Make the paths of all chapters in `items` unique. Chapters that kept the path of their source
+/// file keep it, otherwise the first occurrence of a path wins and later ones get alternatives.
+pub(crate) fn disambiguate(items: &mut [BookItem], style: PathStyle) {
+    fn assign(items: &mut [BookItem], style: PathStyle, used: &mut HashSet<PathBuf>) {
+        for item in items {
+            if let BookItem::Chapter(chapter) = item {
+                let unchanged = chapter.path.is_some() && chapter.path == chapter.source_path;
+
+                if let Some(path) = chapter.path.as_ref().filter(|_| !unchanged) {
+                    if !used.insert(path.clone()) {
+                        let alternative = (2..)
+                            .map(|counter| alternative_path(style, path, chapter, counter))
+                            .find(|alternative| !used.contains(alternative))
+                            .expect("unbounded counter");
+
+                        eprintln!(
+                            "Warning: Chapter \"{}\" would be written to {} which is already \
+                             taken, using {} instead",
+                            chapter.name,
+                            path.display(),
+                            alternative.display()
+                        );
+
+                        used.insert(alternative.clone());
+                        chapter.path = Some(alternative);
+                    }
+                }
+
+                assign(&mut chapter.sub_items, style, used);
+            }
+        }
+    }
+
+    let mut used = HashSet::new();
+    unchanged_paths(items, &mut used);
+    assign(items, style, &mut used);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slugify_headings() {
+        assert_eq!(slugify("Getting Started"), "getting-started");
+        assert_eq!(slugify("  The `foo` API!  "), "the-foo-api");
+        assert_eq!(slugify("Über & Ärger"), "über-ärger");
+        assert_eq!(slugify("?!"), "");
+    }
+}
@@ -0,0 +1,164 @@
+use crate::config::Config;
+use crate::split_book;
+use anyhow::Error;
+use mdbook::book::{Book, BookItem};
+use mdbook::preprocess::CmdPreprocessor;
+use mdbook::MDBook;
+use pulldown_cmark::Event;
+use serde::Serialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Entry of the table of contents after splitting.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Item {
+    Chapter {
+        name: String,
+        /// Path of the chapter, `None` for draft chapters.
+        path: Option<PathBuf>,
+        number: Option<String>,
+        words: usize,
+        sub_items: Vec<Item>,
+    },
+    Separator,
+    PartTitle {
+        title: String,
+    },
+}
+
+/// Table of contents of a book after splitting, without building it.
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct Preview(Vec<Item>);
+
+fn words(content: &str, config: &Config) -> usize {
+    pulldown_cmark::Parser::new_ext(content, config.markdown.parser_options())
+        .map(|event| match event {
+            Event::Text(text) | Event::Code(text) => text.split_whitespace().count(),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn items(items: &[BookItem], config: &Config) -> Vec<Item> {
+    items
+        .iter()
+        .map(|item| match item {
+            BookItem::Chapter(chapter) => Item::Chapter {
+                name: chapter.name.clone(),
+                path: chapter.path.clone(),
+                number: chapter.number.as_ref().map(ToString::to_string),
+                words: words(&chapter.content, config),
+                sub_items: self::items(&chapter.sub_items, config),
+            },
+            BookItem::Separator => Item::Separator,
+            BookItem::PartTitle(title) => Item::PartTitle {
+                title: title.clone(),
+            },
+        })
+        .collect()
+}
+
+impl Preview {
+    /// Split the chapters of `book` for `renderer` the way the preprocessor would.
+    fn new(book: Book, book_config: &mdbook::Config, renderer: &str) -> Result<Self, Error> {
+        let config = Config::from_book_config(book_config, renderer)?;
+        let book = if config.splits_for(renderer) {
+            split_book(book, &config, "html", false)?.0
+        } else {
+            book
+        };
+
+        Ok(Self(items(&book.sections, &config)))
+    }
+
+    /// Preview the book at `root` as it would be split for `renderer`.
+    pub fn load(root: &Path, renderer: &str) -> Result<Self, Error> {
+        let md_book = MDBook::load(root)?;
+        Self::new(md_book.book, &md_book.config, renderer)
+    }
+
+    /// Preview the book in a preprocessor JSON payload as mdBook passes it to preprocessors.
+    pub fn from_json(reader: impl Read) -> Result<Self, Error> {
+        let (ctx, book) = CmdPreprocessor::parse_input(reader)?;
+        Self::new(book, &ctx.config, &ctx.renderer)
+    }
+
+    /// The table of contents as JSON.
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// The table of contents as indented plain text, one chapter per line.
+    pub fn to_text(&self) -> String {
+        fn write(items: &[Item], depth: usize, text: &mut String) {
+            let indent = "  ".repeat(depth);
+
+            for item in items {
+                match item {
+                    Item::Chapter {
+                        name,
+                        path,
+                        number,
+                        words,
+                        sub_items,
+                    } => {
+                        let number = number
+                            .as_ref()
+                            .map(|number| format!("{number} "))
+                            .unwrap_or_default();
+                        let path = path
+                            .as_ref()
+                            .map(|path| path.display().to_string())
+                            .unwrap_or_else(|| "draft".to_string());
+
+                        text.push_str(&format!("{indent}{number}{name} ({path}, {words} words)\n"));
+                        write(sub_items, depth + 1, text);
+                    }
+                    Item::Separator => text.push_str(&format!("{indent}---\n")),
+                    Item::PartTitle { title } => text.push_str(&format!("{indent}# {title}\n")),
+                }
+            }
+        }
+
+        let mut text = String::new();
+        write(&self.0, 0, &mut text);
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mdbook::book::Chapter;
+
+    #[test]
+    fn preview_split_chapters() {
+        let mut book = Book::new();
+        let mut chapter = Chapter::new(
+            "Deck",
+            "# Intro\n\nTwo words.\n\n# Usage {#usage}\n".to_string(),
+            "deck.md",
+            vec![],
+        );
+        chapter.number = Some(vec![1].into_iter().collect());
+        book.push_item(BookItem::PartTitle("Slides".to_string()));
+        book.push_item(chapter);
+
+        let mut config = mdbook::Config::default();
+        config.set("preprocessor.split.path-style", "slug").unwrap();
+
+        let preview = Preview::new(book, &config, "html").unwrap();
+        assert_eq!(
+            preview.to_text(),
+            "# Slides\n\
+             1. Intro (deck/intro.md, 3 words)\n\
+             2. Usage (deck/usage.md, 1 words)\n"
+        );
+        assert!(preview
+            .to_json()
+            .unwrap()
+            .contains("\"type\": \"part-title\""));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::links::Source;
+use mdbook::book::{Book, BookItem, Chapter};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Removes the page break the print page puts before chapters that continue a split source file.
+const STYLE: &str = "<style>\n\
+                     div[style*=\"break-before\"]:has(+ .split-continued) { display: none; }\n\
+                     </style>";
+
+/// Marks a chapter as continuing the source file of the chapter before it.
+const CONTINUED: &str = "<div class=\"split-continued\"></div>";
+
+/// Mark the chapters taken from the same source file after the first one, so the print page
+/// shows them without page breaks in between, like the source file as written. The chapters keep
+/// their content, so no heading or text of the book appears twice.
+pub(crate) fn keep_unsplit(book: &mut Book, sources: &HashMap<PathBuf, Source>) {
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+
+    for item in book.iter() {
+        if let BookItem::Chapter(Chapter {
+            path: Some(_),
+            source_path: Some(source_path),
+            ..
+        }) = item
+        {
+            *counts.entry(source_path.clone()).or_default() += 1;
+        }
+    }
+
+    book.for_each_mut(|item| {
+        let BookItem::Chapter(chapter) = item else {
+            return;
+        };
+        let (Some(path), Some(source_path)) = (&chapter.path, &chapter.source_path) else {
+            return;
+        };
+
+        if counts.get(source_path).copied().unwrap_or_default() < 2 {
+            return;
+        }
+
+        let first = sources
+            .get(source_path)
+            .is_some_and(|source| source.first == *path);
+        let mark = if first { STYLE } else { CONTINUED };
+
+        chapter.content = format!("{mark}\n\n{}", chapter.content);
+    });
+}
@@ -0,0 +1,185 @@
+use crate::links::{normalize, relative_url, Source};
+use anyhow::{bail, Context, Error};
+use mdbook::book::{Book, BookItem};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Redirects from the former output files of split source files to the first chapter taken from
+/// them, in the format of `output.html.redirect`.
+pub(crate) fn redirects(
+    book: &Book,
+    sources: &HashMap<PathBuf, Source>,
+) -> BTreeMap<String, String> {
+    let paths = book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(chapter) => chapter.path.as_ref(),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    sources
+        .iter()
+        .filter(|(source_path, _)| !paths.contains(source_path))
+        .map(|(source_path, source)| {
+            let dir = source_path.parent().unwrap_or(Path::new(""));
+            let original = relative_url(Path::new(""), &source_path.with_extension("html"));
+            let new = relative_url(dir, &source.first.with_extension("html"));
+            (format!("/{original}"), new)
+        })
+        .collect()
+}
+
+/// Write `redirects` merged with the redirects already configured in `config` as an
+/// `[output.html.redirect]` table to `file` relative to the book `root`. The file is only written
+/// if its content changes and never inside the source directory, where writing it would trigger
+/// another build when serving the book.
+pub(crate) fn write(
+    root: &Path,
+    file: &Path,
+    redirects: BTreeMap<String, String>,
+    config: &mdbook::Config,
+) -> Result<(), Error> {
+    let path = normalize(&root.join(file));
+
+    if path.starts_with(normalize(&root.join(&config.book.src))) {
+        bail!(
+            "The redirect file {} must not be inside the source directory",
+            file.display()
+        );
+    }
+
+    let mut merged: BTreeMap<String, String> = config
+        .get_deserialized_opt("output.html.redirect")?
+        .unwrap_or_default();
+
+    for (original, new) in redirects {
+        merged.entry(original).or_insert(new);
+    }
+
+    let table = BTreeMap::from([(
+        "output",
+        BTreeMap::from([("html", BTreeMap::from([("redirect", merged)]))]),
+    )]);
+
+    let content = toml::to_string(&table)?;
+
+    if std::fs::read_to_string(&path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+
+    std::fs::write(&path, content)
+        .with_context(|| format!("Unable to write redirects to {}", path.display()))
+}
+
+/// Append a script to the first chapter taken from each split source file that forwards
+/// visitors arriving with an anchor of the source file to the chapter the anchor moved to.
+pub(crate) fn forward_anchors(book: &mut Book, sources: &HashMap<PathBuf, Source>) {
+    book.for_each_mut(|item| {
+        let BookItem::Chapter(chapter) = item else {
+            return;
+        };
+        let (Some(path), Some(source)) = (
+            &chapter.path,
+            chapter
+                .source_path
+                .as_ref()
+                .and_then(|source_path| sources.get(source_path)),
+        ) else {
+            return;
+        };
+
+        if source.first != *path {
+            return;
+        }
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let anchors = source
+            .anchors
+            .iter()
+            .filter(|(old, (target, new))| target != path || new != *old)
+            .map(|(old, (target, new))| {
+                let url = if target == path {
+                    format!("#{new}")
+                } else {
+                    format!(
+                        "{}#{new}",
+                        relative_url(dir, &target.with_extension("html"))
+                    )
+                };
+                (old, url)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        if anchors.is_empty() {
+            return;
+        }
+
+        let anchors = serde_json::to_string(&anchors)
+            .expect("string map")
+            .replace("</", "<\\/");
+
+        while !chapter.content.ends_with("\n\n") {
+            chapter.content.push('\n');
+        }
+
+        chapter.content.push_str(&format!(
+            "<script>\n\
+             (function () {{\n    \
+                 var anchors = {anchors};\n    \
+                 var id = decodeURIComponent(window.location.hash.slice(1));\n    \
+                 if (anchors.hasOwnProperty(id) && !document.getElementById(id)) {{\n        \
+                     window.location.replace(anchors[id]);\n    \
+                 }}\n\
+             }})();\n\
+             </script>\n"
+        ));
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+    use crate::links::sources;
+    use mdbook::book::Chapter;
+
+    #[test]
+    fn redirect_split_sources() {
+        let mut book = Book::new();
+
+        for (name, path, source_path) in [
+            ("One", "guide/intro/one.md", "guide/intro.md"),
+            ("Two", "guide/intro/two.md", "guide/intro.md"),
+            ("Other", "other.md", "other.md"),
+        ] {
+            let mut chapter = Chapter::new(name, format!("# {name}\n"), path, vec![]);
+            chapter.source_path = Some(PathBuf::from(source_path));
+            book.push_item(chapter);
+        }
+
+        let sources = sources(&book, &Config::default());
+        assert_eq!(
+            redirects(&book, &sources),
+            BTreeMap::from([(
+                "/guide/intro.html".to_string(),
+                "intro/one.html".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    fn reject_redirect_file_in_source_directory() {
+        let config = mdbook::Config::default();
+
+        for file in ["src/redirects.toml", "./other/../src/redirects.toml"] {
+            let result = write(
+                Path::new("/book"),
+                Path::new(file),
+                BTreeMap::new(),
+                &config,
+            );
+            assert!(result.is_err());
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::config::Config;
+use crate::section::Section;
+use pulldown_cmark::{Event, LinkType, Tag};
+
+/// Label of the reference-style link or image written as `source`, with whitespace collapsed the
+/// way link reference definitions are looked up.
+fn reference_label(source: &str, link_type: LinkType) -> Option<String> {
+    let source = source
+        .trim_end()
+        .strip_prefix('!')
+        .unwrap_or(source.trim_end());
+    let inner = source.strip_prefix('[')?.strip_suffix(']')?;
+
+    let label = match link_type {
+        LinkType::Reference => &inner[inner.rfind('[')? + 1..],
+        LinkType::Collapsed => inner.strip_suffix("][")?,
+        LinkType::Shortcut => inner,
+        _ => return None,
+    };
+
+    Some(label.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Append the link reference definitions used by reference-style links of a section but defined
+/// outside of it, so the links keep resolving after the split. Links in content appended to a
+/// section, like footnote definitions, count as well.
+pub(crate) fn propagate(sections: &mut [Section], content: &str, config: &Config) {
+    let parser = pulldown_cmark::Parser::new_ext(content, config.markdown.parser_options());
+    let definitions = parser.reference_definitions();
+    let links = pulldown_cmark::Parser::new_ext(content, config.markdown.parser_options())
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::Link(link_type, _, _) | Tag::Image(link_type, _, _)) => {
+                Some((link_type, range))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    for section in sections {
+        let own_links = section
+            .events
+            .iter()
+            .filter_map(|(event, range)| match event {
+                Event::Start(Tag::Link(link_type, _, _) | Tag::Image(link_type, _, _)) => {
+                    Some((*link_type, range))
+                }
+                _ => None,
+            });
+        let extra_links = links
+            .iter()
+            .filter(|(_, range)| {
+                section
+                    .extra
+                    .iter()
+                    .any(|extra| extra.start <= range.start && range.end <= extra.end)
+            })
+            .map(|(link_type, range)| (*link_type, range));
+
+        let mut spans = own_links
+            .chain(extra_links)
+            .filter_map(|(link_type, range)| {
+                let label = reference_label(&content[range.clone()], link_type)?;
+                Some(definitions.get(&label)?.span.clone())
+            })
+            .collect::<Vec<_>>();
+        spans.sort_by_key(|span| span.start);
+        spans.dedup();
+
+        for span in spans {
+            let defined_in_section =
+                section.range.start <= span.start && span.end <= section.range.end;
+
+            if !defined_in_section && !section.extra.contains(&span) {
+                section.extra.push(span);
+            }
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use crate::config::{Config, Preamble};
+use crate::directive::Directive;
+use crate::front_matter::{self, FrontMatter};
+use pulldown_cmark::{Event, HeadingLevel, Tag};
+use std::borrow::Borrow;
+use std::ops::Range;
+
+/// Level of the heading started by `event` if the chapter is split at it, which a `.nosplit`
+/// class prevents.
+pub(crate) fn split_level(event: &Event, config: &Config) -> Option<HeadingLevel> {
+    match event {
+        Event::Start(Tag::Heading(level, _, classes))
+            if config.level.contains(*level) && !classes.contains(&"nosplit") =>
+        {
+            Some(*level)
+        }
+        _ => None,
+    }
+}
+
+/// Plain text of a heading without any formatting, taken from the `events` following its start.
+pub(crate) fn heading_text<'a, E: Borrow<Event<'a>>>(
+    events: impl IntoIterator<Item = E>,
+) -> String {
+    let mut text = String::new();
+
+    for event in events {
+        match event.borrow() {
+            Event::End(Tag::Heading(..)) => break,
+            Event::Text(content) | Event::Code(content) => text.push_str(content),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+
+    text
+}
+
+/// Consecutive part of a chapter's source that becomes a chapter of its own.
+pub(crate) struct Section<'a> {
+    /// Level of the heading that starts the section, `None` for content before the first one.
+    /// Sections started by a split marker take the level of the section before them.
+    pub level: Option<HeadingLevel>,
+    /// Whether the section starts at a split marker instead of a heading.
+    pub marker: bool,
+    /// Front matter after the section's heading, whose range is part of `skip`.
+    pub front_matter: Option<FrontMatter>,
+    pub events: Vec<(Event<'a>, Range<usize>)>,
+    /// Range of the section within the chapter's source.
+    pub range: Range<usize>,
+    /// Ranges of the chapter's source left out of the section's content.
+    pub skip: Vec<Range<usize>>,
+    /// Ranges of the chapter's source appended to the section's content.
+    pub extra: Vec<Range<usize>>,
+}
+
+impl<'a> Section<'a> {
+    fn new(level: Option<HeadingLevel>, marker: bool, start: usize) -> Self {
+        Self {
+            level,
+            marker,
+            front_matter: None,
+            events: vec![],
+            range: start..start,
+            skip: vec![],
+            extra: vec![],
+        }
+    }
+
+    /// Index of the start of the heading that starts the section.
+    fn heading_start(&self) -> Option<usize> {
+        let level = self.level.filter(|_| !self.marker)?;
+
+        self.events.iter().position(
+            |(event, _)| matches!(event, Event::Start(Tag::Heading(l, ..)) if *l == level),
+        )
+    }
+
+    /// Plain text of the heading that starts the section, without any formatting.
+    pub fn heading_text(&self) -> Option<String> {
+        let start = self.heading_start()?;
+
+        Some(heading_text(
+            self.events[start + 1..].iter().map(|(event, _)| event),
+        ))
+    }
+
+    /// Explicit id and classes of the heading that starts the section, given as attributes like
+    /// `{#id .class}`.
+    pub fn heading_attributes(&self) -> (Option<&'a str>, &[&'a str]) {
+        match self.heading_start().map(|start| &self.events[start].0) {
+            Some(Event::Start(Tag::Heading(_, id, classes))) => (*id, classes),
+            _ => (None, &[]),
+        }
+    }
+
+    /// Number of words in the section's text.
+    pub fn words(&self) -> usize {
+        self.events
+            .iter()
+            .map(|(event, _)| match event {
+                Event::Text(text) | Event::Code(text) => text.split_whitespace().count(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Content of the section taken from the chapter's `source`.
+    pub fn content(&self, source: &str) -> String {
+        let mut skip = self.skip.clone();
+        skip.sort_by_key(|range| range.start);
+
+        let mut content = String::new();
+        let mut start = self.range.start;
+
+        for range in skip {
+            content.push_str(&source[start..range.start]);
+            start = range.end;
+        }
+
+        content.push_str(&source[start..self.range.end]);
+
+        for range in &self.extra {
+            while !content.is_empty() && !content.ends_with("\n\n") {
+                content.push('\n');
+            }
+
+            content.push_str(source[range.clone()].trim_end());
+            content.push('\n');
+        }
+
+        content
+    }
+}
+
+/// Returns `true` if `event` is a split marker, i.e. a rule if `split-on-rule` is enabled or the
+/// configured marker comment.
+fn is_marker(event: &Event, config: &Config) -> bool {
+    match event {
+        Event::Rule => config.split_on_rule,
+        Event::Html(html) => config
+            .marker
+            .as_ref()
+            .is_some_and(|marker| html.trim() == marker.trim()),
+        _ => false,
+    }
+}
+
+/// Front matter following a heading that ends at `start`. A block that doesn't parse stays part
+/// of the content, since it may just as well be a thematic break followed by a setext heading.
+fn read_front_matter(content: &str, start: usize) -> Option<(Range<usize>, FrontMatter)> {
+    let block = front_matter::find(content, start)?;
+
+    match FrontMatter::parse(&content[block.clone()]) {
+        Ok(parsed) => Some((block, parsed)),
+        Err(e) => {
+            let line = content[..block.start].lines().count() + 1;
+            eprintln!("Warning: Keeping invalid front matter after line {line} as content: {e}");
+            None
+        }
+    }
+}
+
+/// Cut `content` into sections starting at top-level split headings and split markers, leaving
+/// out directives, markers and front matter.
+pub(crate) fn sections<'a>(content: &'a str, config: &Config) -> Vec<Section<'a>> {
+    let parser = pulldown_cmark::Parser::new_ext(content, config.markdown.parser_options());
+    let mut sections = vec![];
+    let mut current = Section::new(None, false, 0);
+    let mut nosplit = false;
+    let mut depth = 0;
+    let mut front_matter: Option<Range<usize>> = None;
+
+    for (event, range) in parser.into_offset_iter() {
+        let in_front_matter = front_matter
+            .as_ref()
+            .is_some_and(|block| block.start <= range.start && range.end <= block.end);
+
+        if in_front_matter {
+            continue;
+        }
+
+        let top_level = depth == 0;
+
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+
+        if !top_level {
+            current.events.push((event, range));
+            continue;
+        }
+
+        if let Some(directive) = Directive::from_event(&event) {
+            nosplit = directive == Directive::NoSplit;
+            current.skip.push(range);
+            continue;
+        }
+
+        if is_marker(&event, config) {
+            if current.events.is_empty() {
+                current.skip.push(range);
+            } else {
+                let next = Section::new(current.level, true, range.end);
+                let mut previous = std::mem::replace(&mut current, next);
+                previous.range.end = range.start;
+                sections.push(previous);
+            }
+
+            continue;
+        }
+
+        // A `nosplit` directive is consumed by the next split heading.
+        let level = split_level(&event, config).filter(|_| !std::mem::take(&mut nosplit));
+
+        if let Some(level) = level {
+            if current.events.is_empty() {
+                current.level = Some(level);
+                current.marker = false;
+            } else {
+                let next = Section::new(Some(level), false, range.start);
+                let mut previous = std::mem::replace(&mut current, next);
+                previous.range.end = range.start;
+                sections.push(previous);
+            }
+
+            front_matter = None;
+
+            if config.front_matter {
+                if let Some((block, parsed)) = read_front_matter(content, range.end) {
+                    current.skip.push(block.clone());
+                    current.front_matter = Some(parsed);
+                    front_matter = Some(block);
+                }
+            }
+        }
+
+        current.events.push((event, range));
+    }
+
+    if !current.events.is_empty() {
+        current.range.end = content.len();
+        sections.push(current);
+    }
+
+    sections
+}
+
+/// Apply the `preamble` policy to the content before the first split heading.
+pub(crate) fn handle_preamble(sections: &mut Vec<Section>, preamble: Preamble) {
+    if sections.len() < 2 || sections[0].level.is_some() {
+        return;
+    }
+
+    match preamble {
+        Preamble::Attach => {
+            let mut preamble = sections.remove(0);
+            let first = &mut sections[0];
+            first.range.start = preamble.range.start;
+            preamble.events.append(&mut first.events);
+            first.events = preamble.events;
+            first.skip.append(&mut preamble.skip);
+        }
+        Preamble::Intro => {}
+        Preamble::Drop => {
+            sections.remove(0);
+        }
+    }
+}
+
+/// Split sections with more than `max_words` words at the shallowest top-level headings below
+/// their own level, repeating until they fit or have no such headings left.
+pub(crate) fn split_long(sections: &mut Vec<Section>, max_words: usize) {
+    let mut result = vec![];
+    let mut pending = std::mem::take(sections);
+    pending.reverse();
+
+    while let Some(section) = pending.pop() {
+        if section.words() <= max_words {
+            result.push(section);
+            continue;
+        }
+
+        let mut parts = split_deeper(section);
+
+        if parts.len() == 1 {
+            result.append(&mut parts);
+        } else {
+            pending.extend(parts.into_iter().rev());
+        }
+    }
+
+    *sections = result;
+}
+
+/// Split `section` at the shallowest top-level headings below its own level that don't start it,
+/// leaving it whole if there are none.
+fn split_deeper(mut section: Section) -> Vec<Section> {
+    let mut depth = 0;
+    let mut headings = vec![];
+
+    for (index, (event, _)) in section.events.iter().enumerate() {
+        if let Event::Start(Tag::Heading(level, _, classes)) = event {
+            let deeper = section.level.is_none_or(|own| *level > own);
+
+            if depth == 0 && index > 0 && deeper && !classes.contains(&"nosplit") {
+                headings.push((index, *level));
+            }
+        }
+
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+    }
+
+    let Some(level) = headings.iter().map(|(_, level)| *level).min() else {
+        return vec![section];
+    };
+
+    let mut parts = vec![];
+
+    for (index, _) in headings.into_iter().rev().filter(|(_, l)| *l == level) {
+        let events = section.events.split_off(index);
+        let start = events[0].1.start;
+        let mut part = Section::new(Some(level), false, start);
+        part.range.end = section.range.end;
+        part.events = events;
+        (part.skip, section.skip) = section
+            .skip
+            .into_iter()
+            .partition(|range| range.start >= start);
+        section.range.end = start;
+        parts.push(part);
+    }
+
+    parts.push(section);
+    parts.reverse();
+    parts
+}
+
+/// Merge sections with fewer than `min_words` words into the section before them.
+pub(crate) fn merge_short(sections: &mut Vec<Section>, min_words: usize) {
+    let mut result: Vec<Section> = vec![];
+
+    for mut section in std::mem::take(sections) {
+        match result.last_mut() {
+            Some(previous) if section.words() < min_words => {
+                previous.range.end = section.range.end;
+                previous.events.append(&mut section.events);
+                previous.skip.append(&mut section.skip);
+            }
+            _ => result.push(section),
+        }
+    }
+
+    *sections = result;
+}